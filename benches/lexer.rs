@@ -0,0 +1,76 @@
+//! Lexes a large generated program with the scanner and with the regex lexer
+//! it replaced, run with `cargo bench --bench lexer`.
+#[path = "../tests/regex_lexer/mod.rs"]
+mod regex_lexer;
+
+use std::time::{Duration, Instant};
+
+use compiler::lexer;
+
+const FUNCTIONS: usize = 1000;
+// The regex lexer compiling a regex for every token type it tries, it lexes a
+// smaller program for the bench to end in seconds
+const BASELINE_FUNCTIONS: usize = 100;
+const RUNS: u32 = 10;
+
+// Program using every kind of token, about 10 lines per function
+fn generate_source(functions: usize) -> String {
+    let mut source = vec!["{".to_owned()];
+    for i in 0..functions {
+        source.push(format!("    fn compute_{}(value, limit) {{", i));
+        source.push(format!("        var total_{} = value * {} + (limit - 3) % 7;", i, i));
+        source.push("        for (var j = 0; j < limit; j = j + 1) {".to_owned());
+        source.push("            if (j == 2 || total >= 10 && !done) { continue; }".to_owned());
+        source.push(format!("            total_{} = total_{} / 2;", i, i));
+        source.push("        }".to_owned());
+        source.push("        for k in 0..limit { print('iteration'); }".to_owned());
+        source.push(format!("        while (total_{} != 0) {{ break; }}", i));
+        source.push(format!("        return total_{} <= limit;", i));
+        source.push("    }".to_owned());
+    }
+    source.push("}".to_owned());
+    source.join("\n")
+}
+
+// Average time taken to lex the source over the runs, with the tokens
+fn time<T>(runs: u32, lex: impl Fn() -> T) -> (Duration, T) {
+    let start = Instant::now();
+    let mut tokens = lex();
+    for _ in 1..runs {
+        tokens = lex();
+    }
+    (start.elapsed() / runs, tokens)
+}
+
+fn main() {
+    let source = generate_source(FUNCTIONS);
+    let lines = source.lines().count();
+    let (elapsed, tokens) = time(RUNS, || {
+        let mut lexer = lexer::LexicalParser::new(source.clone(), "bench.toy");
+        let (lexicon, errors) = lexer.parse();
+        assert!(errors.is_empty());
+        lexicon
+    });
+    let baseline_source = generate_source(BASELINE_FUNCTIONS);
+    let baseline_lines = baseline_source.lines().count();
+    let (baseline, baseline_tokens) = time(1, || regex_lexer::lex(&baseline_source));
+    let (scanned, _) = lexer::LexicalParser::new(baseline_source.clone(), "bench.toy").parse();
+    assert_eq!(regex_lexer::describe(&scanned), regex_lexer::describe(&baseline_tokens));
+    let speed = lines as f64 / elapsed.as_secs_f64();
+    let baseline_speed = baseline_lines as f64 / baseline.as_secs_f64();
+    println!(
+        "scanner: lexed {} lines into {} tokens in {:.2?} ({:.0} lines/s)",
+        lines,
+        tokens.len(),
+        elapsed,
+        speed
+    );
+    println!(
+        "regex lexer: lexed {} lines into the same {} tokens in {:.2?} ({:.0} lines/s), {:.0} times slower",
+        baseline_lines,
+        baseline_tokens.len(),
+        baseline,
+        baseline_speed,
+        speed / baseline_speed
+    );
+}
@@ -1,37 +1,181 @@
-use std::fmt;
+use std::fmt::Write;
 
-pub struct LexicalError{
-    pub line: i32,
-    pub col: i32,
-    pub message: String,
-}
+use crate::span::Span;
 
-impl fmt::Display for LexicalError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Lexical error at l.{} , c.{}; {}", self.line + 1, self.col, self.message)
-    }
-}
+// Error codes, grouped by the phase reporting them
+pub const INVALID_TOKEN: &str = "E0001";
+pub const UNTERMINATED_STRING: &str = "E0002";
+pub const UNTERMINATED_COMMENT: &str = "E0003";
+pub const INVALID_ESCAPE: &str = "E0004";
+pub const SYNTAX_ERROR: &str = "E0100";
+pub const UNDECLARED_IDENTIFIER: &str = "E0101";
+pub const ALREADY_DECLARED: &str = "E0102";
+pub const ARGUMENT_COUNT: &str = "E0103";
+pub const MISPLACED_CONTROL_FLOW: &str = "E0104";
+pub const ASSIGNMENT_TO_FUNCTION: &str = "E0105";
+pub const INVALID_NUMBER: &str = "E0106";
+pub const UNKNOWN_FIELD: &str = "E0107";
+pub const MISSING_FIELDS: &str = "E0108";
+pub const NON_EXHAUSTIVE_MATCH: &str = "E0109";
+pub const TYPE_MISMATCH: &str = "E0200";
+pub const DIVISION_BY_ZERO: &str = "E0201";
+pub const NOT_CALLABLE: &str = "E0202";
+pub const CALL_DEPTH: &str = "E0203";
+pub const MISSING_RETURN_VALUE: &str = "E0204";
+pub const INTEGER_OVERFLOW: &str = "E0205";
+pub const INDEX_OUT_OF_BOUNDS: &str = "E0206";
+pub const KEY_NOT_FOUND: &str = "E0207";
+pub const FIELD_NOT_FOUND: &str = "E0208";
+pub const NO_MATCHING_ARM: &str = "E0209";
+pub const INTERNAL_ERROR: &str = "E0210";
+pub const UNREACHABLE_CODE: &str = "W0001";
 
-impl fmt::Debug for LexicalError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{{ file: {}, line: {} }}", file!(), line!())
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+impl Severity {
+    fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+    // ANSI color of the severity and of the underline of primary labels
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Error => "31",
+            Severity::Warning => "33",
+        }
     }
 }
 
-pub struct SyntaxError{
-    pub line: i32,
-    pub col: i32,
+/// Span of source code annotated with a message, which may be empty.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
     pub message: String,
+    /// Whether the label points at the cause of the diagnostic rather than
+    /// giving context
+    pub primary: bool,
 }
 
-impl fmt::Display for SyntaxError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Syntax error at l.{}, c.{}; {}", self.line + 1, self.col, self.message)
-    }
+/// Error or warning reported by any phase of the compiler, pointing at the
+/// source code it is about.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    /// Primary label first, then secondary ones
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
 }
+impl Diagnostic {
+    pub fn error(code: &'static str, message: &str, span: Span) -> Self {
+        Diagnostic::new(Severity::Error, code, message, span)
+    }
+    pub fn warning(code: &'static str, message: &str, span: Span) -> Self {
+        Diagnostic::new(Severity::Warning, code, message, span)
+    }
+    fn new(severity: Severity, code: &'static str, message: &str, span: Span) -> Self {
+        Diagnostic {
+            severity,
+            code,
+            message: message.to_owned(),
+            labels: vec![Label {
+                span,
+                message: String::new(),
+                primary: true,
+            }],
+            notes: vec![],
+            help: None,
+        }
+    }
+    /// Message shown under the primary span.
+    pub fn with_label(mut self, message: &str) -> Self {
+        self.labels[0].message = message.to_owned();
+        self
+    }
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.to_owned(),
+            primary: false,
+        });
+        self
+    }
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_owned());
+        self
+    }
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_owned());
+        self
+    }
 
-impl fmt::Debug for SyntaxError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{{ file: {}, line: {} }}", file!(), line!())
+    /// Render the diagnostic like rustc does, with every labeled line of the
+    /// source underlined, primary spans with `^` and secondary ones with `-`.
+    pub fn render(&self, source: &str, color: bool) -> String {
+        let paint = |text: &str, style: &str| {
+            if color {
+                format!("\x1b[{}m{}\x1b[0m", style, text)
+            } else {
+                text.to_owned()
+            }
+        };
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|label| (label.span.start, !label.primary));
+        let last_line = labels.iter().map(|label| label.span.start.0 + 1).max().unwrap_or(1);
+        let gutter = " ".repeat(last_line.to_string().len());
+        let bar = paint("|", "1;34");
+
+        let mut output = String::new();
+        let severity = format!("{}[{}]", self.severity.name(), self.code);
+        let _ = writeln!(output, "{}{}", paint(&severity, &format!("1;{}", self.severity.color())), paint(&format!(": {}", self.message), "1"));
+        let _ = writeln!(output, "{}{} {}", gutter, paint("-->", "1;34"), self.labels[0].span);
+        let _ = writeln!(output, "{} {}", gutter, bar);
+        let mut previous_line: Option<i32> = None;
+        for label in labels {
+            let line = label.span.start.0;
+            let text = source.lines().nth(line as usize).unwrap_or_default();
+            // Labels on the same line share its snippet
+            if previous_line != Some(line) {
+                let number = format!("{:>width$}", line + 1, width = gutter.len());
+                let _ = writeln!(output, "{} {} {}", paint(&number, "1;34"), bar, text);
+                previous_line = Some(line);
+            }
+            // Columns are byte offsets, the underline is aligned on characters
+            // and tabs are kept so it matches the width of the source
+            let start = (label.span.start.1 as usize).min(text.len());
+            let offset: String = text
+                .get(..start)
+                .unwrap_or_default()
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            // Spans over several lines are underlined up to the end of the first one
+            let end = if label.span.end.0 == line { (label.span.end.1 as usize).min(text.len()) } else { text.len() };
+            let width = text.get(start..end).map_or(0, |spanned| spanned.chars().count()).max(1);
+            let (mark, style) = if label.primary {
+                ("^", format!("1;{}", self.severity.color()))
+            } else {
+                ("-", "1;34".to_owned())
+            };
+            let underline = format!("{} {}", mark.repeat(width), label.message);
+            let _ = writeln!(output, "{} {} {}{}", gutter, bar, offset, paint(underline.trim_end(), &style));
+        }
+        if !self.notes.is_empty() || self.help.is_some() {
+            let _ = writeln!(output, "{} {}", gutter, bar);
+        }
+        for note in &self.notes {
+            let _ = writeln!(output, "{} {} {}", gutter, paint("= note:", "1"), note);
+        }
+        if let Some(help) = &self.help {
+            let _ = writeln!(output, "{} {} {}", gutter, paint("= help:", "1"), help);
+        }
+        output
     }
-}
\ No newline at end of file
+}
@@ -1,15 +1,41 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::scope::ScopeRef;
+use crate::span::Span;
 
 #[derive(Debug, Clone)]
 pub struct StatementBlock {
     pub statements: Vec<Statement>,
-    pub symbol_table: HashMap<String, Identifier>,
 }
 
 #[derive(Debug, Clone)]
-pub enum Statement {
+pub struct Statement {
+    pub span: Span,
+    pub kind: StatementKind,
+}
+#[derive(Debug, Clone)]
+pub enum StatementKind {
+    Declaration(DeclarationStatement),
     Assignment(AssignmentStatement),
+    IndexAssignment(IndexAssignment),
+    /// Removal of an element of a list or an entry of a map
+    Delete(Index),
+    FieldAssignment(FieldAssignment),
+    // Only checked by the parser, there being nothing to run
+    Struct(StructDeclaration),
+    Enum(EnumDeclaration),
     If(IfStatement),
+    While(WhileStatement),
+    For(ForStatement),
+    ForIn(ForInStatement),
+    Break,
+    Continue,
+    Function(FunctionDeclaration),
+    Return(Option<Expression>),
+    Expression(Expression),
     Print(PrintStatement),
 }
 #[derive(Debug, Clone)]
@@ -24,14 +50,81 @@ pub struct IfStatement {
     pub else_statement_block: Option<StatementBlock>,
 }
 #[derive(Debug, Clone)]
+pub struct WhileStatement {
+    pub expression: Expression,
+    pub statement_block: StatementBlock,
+}
+#[derive(Debug, Clone)]
+pub struct ForStatement {
+    pub initialization: DeclarationStatement,
+    pub condition: Expression,
+    pub update: AssignmentStatement,
+    pub statement_block: StatementBlock,
+}
+#[derive(Debug, Clone)]
+pub struct ForInStatement {
+    pub identifier: Identifier,
+    pub iterable: Iterable,
+    pub statement_block: StatementBlock,
+}
+#[derive(Debug, Clone)]
+pub enum Iterable {
+    /// Integers from the start included to the end excluded
+    Range(Expression, Expression),
+    /// Elements of a list or keys of a map, as they are when the loop starts
+    Collection(Expression),
+}
+#[derive(Debug, Clone)]
+pub struct FunctionDeclaration {
+    pub identifier: Identifier,
+    pub parameters: Vec<Identifier>,
+    pub statement_block: StatementBlock,
+}
+#[derive(Debug, Clone)]
+pub struct DeclarationStatement {
+    pub identifier: Identifier,
+    pub expression: Expression,
+}
+#[derive(Debug, Clone)]
 pub struct AssignmentStatement {
     pub identifier: Identifier,
     pub expression: Expression,
 }
+/// Assignment to an element of a list or to the value of a map key,
+/// `collection[index] = expression`.
+#[derive(Debug, Clone)]
+pub struct IndexAssignment {
+    pub target: Index,
+    pub expression: Expression,
+}
+/// Assignment to a field of a struct, `target.field = expression`.
+#[derive(Debug, Clone)]
+pub struct FieldAssignment {
+    pub target: Field,
+    pub expression: Expression,
+}
+#[derive(Debug, Clone)]
+pub struct StructDeclaration {
+    pub identifier: Identifier,
+    pub fields: Vec<Identifier>,
+}
+#[derive(Debug, Clone)]
+pub struct EnumDeclaration {
+    pub identifier: Identifier,
+    pub variants: Vec<VariantDeclaration>,
+}
+/// Variant of an enum, with the names of the values it holds.
+#[derive(Debug, Clone)]
+pub struct VariantDeclaration {
+    pub identifier: Identifier,
+    pub fields: Vec<Identifier>,
+}
 #[derive(Debug, Clone)]
 pub enum Operator {
     And,
     Or,
+    /// `??`, giving its right operand when its left one is nil
+    Coalesce,
     Modulo,
     Plus,
     Minus,
@@ -44,32 +137,367 @@ pub enum Operator {
     Superior,
     SupOrEqual,
 }
+impl Operator {
+    /// Binding power of the operator, higher binds tighter.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Operator::Coalesce => 1,
+            Operator::Or => 2,
+            Operator::And => 3,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::Inferior
+            | Operator::InfOrEqual
+            | Operator::Superior
+            | Operator::SupOrEqual => 4,
+            Operator::Plus | Operator::Minus => 5,
+            Operator::Multiplication | Operator::Division | Operator::Modulo => 6,
+        }
+    }
+}
+#[derive(Debug, Clone)]
+pub enum UnaryOperator {
+    Not,
+    Negation,
+}
 #[derive(Debug, Clone)]
 pub struct Identifier {
+    pub span: Span,
     pub name: String,
-    pub value: Option<Value>,
 }
 #[derive(Debug, Clone)]
 pub enum Value {
+    /// Absence of a value, only equal to itself
+    Nil,
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Bool(bool),
+    Function(Rc<Closure>),
+    List(ListRef),
+    Map(MapRef),
+    Struct(StructRef),
+    Variant(Rc<Variant>),
+}
+impl Value {
+    /// Name of the type of the value, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Bool(_) => "boolean",
+            Value::Function(_) => "function",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Struct(_) => "struct",
+            Value::Variant(_) => "enum",
+        }
+    }
+}
+// Conversion of values to text, used when printing and interpolating them
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", text(self, &mut vec![]))
+    }
+}
+// Text of a value, given the addresses of the lists, maps and instances whose
+// text contains it. Being shared, they may contain themselves, in which case
+// the inner occurrence prints as `[...]`, `{...}` or `Point { ... }`.
+fn text(value: &Value, printing: &mut Vec<*const ()>) -> String {
+    match value {
+        Value::Nil => "nil".to_owned(),
+        Value::Integer(int) => int.to_string(),
+        // Shortest text reading back as the same float, whole numbers
+        // keeping a `.0` so they are not mistaken for integers
+        Value::Float(float) => format!("{:?}", float),
+        Value::String(string) => string.clone(),
+        Value::Bool(b) => b.to_string(),
+        Value::Function(function) => format!("{:?}", function),
+        Value::List(list) => nested(printing, Rc::as_ptr(list).cast(), "[...]".to_owned(), |printing| {
+            let elements: Vec<String> = list.borrow().iter().map(|element| quoted(element, printing)).collect();
+            format!("[{}]", elements.join(", "))
+        }),
+        Value::Map(map) => nested(printing, Rc::as_ptr(map).cast(), "{...}".to_owned(), |printing| {
+            let entries: Vec<String> = map
+                .borrow()
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, quoted(value, printing)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }),
+        Value::Struct(instance) => {
+            let instance_ref = instance.borrow();
+            let cycle = format!("{} {{ ... }}", instance_ref.name);
+            nested(printing, Rc::as_ptr(instance).cast(), cycle, |printing| {
+                let fields: Vec<String> = instance_ref
+                    .fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, quoted(value, printing)))
+                    .collect();
+                format!("{} {{ {} }}", instance_ref.name, fields.join(", "))
+            })
+        },
+        Value::Variant(variant) if variant.values.is_empty() => format!("{}.{}", variant.enum_name, variant.name),
+        Value::Variant(variant) => {
+            let values: Vec<String> = variant.values.iter().map(|value| quoted(value, printing)).collect();
+            format!("{}.{}({})", variant.enum_name, variant.name, values.join(", "))
+        },
+    }
+}
+// Text of a list, map or instance at the address, or the text standing for
+// it if it is already being printed
+fn nested(printing: &mut Vec<*const ()>, address: *const (), cycle: String, text: impl FnOnce(&mut Vec<*const ()>) -> String) -> String {
+    if printing.contains(&address) {
+        return cycle;
+    }
+    printing.push(address);
+    let text = text(printing);
+    printing.pop();
+    text
+}
+// Text of a value inside a collection, strings being quoted so that `['1']`
+// and `[1]` look different
+fn quoted(value: &Value, printing: &mut Vec<*const ()>) -> String {
+    match value {
+        Value::String(string) => format!("{:?}", string),
+        value => text(value, printing),
+    }
+}
+/// Value usable as a map key, keys being equal when their values are.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
     Integer(i64),
     String(String),
     Bool(bool),
 }
+impl Key {
+    /// Key of the same value, or `None` for values that cannot be keys.
+    pub fn from_value(value: &Value) -> Option<Key> {
+        match value {
+            Value::Integer(int) => Some(Key::Integer(*int)),
+            Value::String(string) => Some(Key::String(string.clone())),
+            Value::Bool(b) => Some(Key::Bool(*b)),
+            _ => None,
+        }
+    }
+    pub fn to_value(&self) -> Value {
+        match self {
+            Key::Integer(int) => Value::Integer(*int),
+            Key::String(string) => Value::String(string.clone()),
+            Key::Bool(b) => Value::Bool(*b),
+        }
+    }
+}
+// Keys are shown as they are inside a printed map
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", quoted(&self.to_value(), &mut vec![]))
+    }
+}
+/// Entries of a map, kept in insertion order so that printing and iterating
+/// over a map are deterministic, and indexed by key.
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    // Removed entries leave a hole, until holes outnumber entries and are
+    // dropped all at once so that removing stays constant time on average
+    entries: Vec<Option<(Key, Value)>>,
+    // Position of the entry of each key in the map
+    positions: HashMap<Key, usize>,
+}
+impl Map {
+    pub fn get(&self, key: &Key) -> Option<&Value> {
+        let position = *self.positions.get(key)?;
+        self.entries[position].as_ref().map(|(_, value)| value)
+    }
+    /// Replace the value of the key, which keeps its place, or add it last.
+    pub fn insert(&mut self, key: Key, value: Value) {
+        match self.positions.get(&key) {
+            Some(&position) => self.entries[position] = Some((key, value)),
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push(Some((key, value)));
+            },
+        }
+    }
+    pub fn remove(&mut self, key: &Key) -> Option<Value> {
+        let position = self.positions.remove(key)?;
+        let (_, value) = self.entries[position].take()?;
+        if self.entries.len() > 2 * self.positions.len() {
+            self.entries.retain(Option::is_some);
+            for (position, (key, _)) in self.entries.iter().flatten().enumerate() {
+                self.positions.insert(key.clone(), position);
+            }
+        }
+        Some(value)
+    }
+    /// Entries in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = &(Key, Value)> {
+        self.entries.iter().flatten()
+    }
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.iter().map(|(key, _)| key)
+    }
+}
+/// Entries of a map, shared like the elements of a list.
+pub type MapRef = Rc<RefCell<Map>>;
+/// Instance of a struct, with its fields in declaration order.
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub name: String,
+    pub fields: Vec<(String, Value)>,
+}
+impl Instance {
+    /// Position of the field, or `None` if the struct has no such field.
+    pub fn position(&self, field: &str) -> Option<usize> {
+        self.fields.iter().position(|(name, _)| name == field)
+    }
+}
+/// Value of an enum, which cannot change once created.
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub enum_name: String,
+    pub name: String,
+    pub values: Vec<Value>,
+}
+/// Fields of a struct instance, shared like the elements of a list.
+pub type StructRef = Rc<RefCell<Instance>>;
+/// Elements of a list, shared by every copy of the value so that changes
+/// made through one of them are seen by all.
+pub type ListRef = Rc<RefCell<Vec<Value>>>;
+/// Function value, evaluated in a child of the environment it was created in.
+#[derive(Clone)]
+pub struct Closure {
+    pub parameters: Vec<Identifier>,
+    pub statement_block: StatementBlock,
+    pub environment: ScopeRef<Value>,
+}
+// The captured environment may hold the closure itself, so it is left out
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parameters: Vec<&str> = self.parameters.iter().map(|p| p.name.as_str()).collect();
+        write!(f, "fn({})", parameters.join(", "))
+    }
+}
+#[derive(Debug, Clone)]
+pub struct Expression {
+    pub span: Span,
+    pub kind: ExpressionKind,
+}
 #[derive(Debug, Clone)]
-pub enum Expression {
+pub enum ExpressionKind {
     Operation(Box<Operation>),
+    Unary(Box<Unary>),
+    Call(Box<Call>),
+    List(Vec<Expression>),
+    /// Keys and values of the entries of a map, in order
+    Map(Vec<(Expression, Expression)>),
+    Index(Box<Index>),
+    Struct(StructLiteral),
+    Field(Box<Field>),
+    Variant(VariantLiteral),
+    Match(Box<Match>),
+    Function(Box<FunctionLiteral>),
+    /// String made of its parts converted to text and joined
+    Interpolation(Vec<InterpolationPart>),
     Term(Term),
 }
 #[derive(Debug, Clone)]
+pub enum InterpolationPart {
+    Text(String),
+    Expression(Expression),
+}
+#[derive(Debug, Clone)]
 pub struct Operation {
     pub left: Expression,
     pub operator: Operator,
     pub right: Expression,
 }
 #[derive(Debug, Clone)]
-pub enum Term {
+pub struct Unary {
+    pub operator: UnaryOperator,
+    pub operand: Expression,
+}
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub callee: Expression,
+    pub arguments: Vec<Expression>,
+}
+#[derive(Debug, Clone)]
+pub struct Index {
+    pub collection: Expression,
+    pub index: Expression,
+}
+/// Instance of a struct, with a value for each of its fields in declaration
+/// order.
+#[derive(Debug, Clone)]
+pub struct StructLiteral {
+    pub identifier: Identifier,
+    pub fields: Vec<(Identifier, Expression)>,
+}
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub target: Expression,
+    pub field: Identifier,
+}
+/// Value of an enum, `Enum.Variant` or `Enum.Variant(arguments)`.
+#[derive(Debug, Clone)]
+pub struct VariantLiteral {
+    pub enum_name: Identifier,
+    pub variant: Identifier,
+    pub arguments: Vec<Expression>,
+}
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub expression: Expression,
+    pub arms: Vec<MatchArm>,
+}
+/// Arm of a match, whose expression is evaluated with the bindings of its
+/// pattern when it is the first one matching.
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub expression: Expression,
+}
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub span: Span,
+    pub kind: PatternKind,
+}
+#[derive(Debug, Clone)]
+pub enum PatternKind {
+    /// `_`, matching any value
+    Wildcard,
+    /// Identifier matching any value and bound to it
+    Binding(Identifier),
+    /// Integer, float, string, boolean or nil literal matching equal values
+    Literal(TermKind),
+    Variant(VariantPattern),
+}
+/// Variant of an enum whose values match the patterns of its fields.
+#[derive(Debug, Clone)]
+pub struct VariantPattern {
+    pub enum_name: String,
+    pub variant: Identifier,
+    pub fields: Vec<Pattern>,
+}
+#[derive(Debug, Clone)]
+pub struct FunctionLiteral {
+    pub parameters: Vec<Identifier>,
+    pub statement_block: StatementBlock,
+}
+#[derive(Debug, Clone)]
+pub struct Term {
+    pub span: Span,
+    pub kind: TermKind,
+}
+#[derive(Debug, Clone)]
+pub enum TermKind {
+    Nil,
     Bool(bool),
     Integer(i64),
+    Float(f64),
     String(String),
     Identifier(Identifier),
 }
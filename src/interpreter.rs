@@ -1,134 +1,722 @@
-use std::collections::HashMap;
-
-use crate::grammar::{StatementBlock, Statement, PrintStatement, Term, Expression, Value, Identifier, Operator};
-
-pub fn interpret(ast: StatementBlock) {
-    let mut var_table = ast.symbol_table;
-    for statement in ast.statements {
-        match statement {
-            Statement::If(if_statement) => {
-                let value = interpret_expression(&var_table, if_statement.expression);
-                match value {
-                    Value::Bool(b) => {
-                        if b {
-                            interpret(if_statement.then_statement_block)
-                        } else {
-                            match if_statement.else_statement_block {
-                                Some(block) => interpret(block),
-                                None => {}
-                            } 
-                        }
-                    },
-                    _ => panic!("If condition must return a boolean")
-                }
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::errors::{
+    Diagnostic, ARGUMENT_COUNT, CALL_DEPTH, DIVISION_BY_ZERO, INDEX_OUT_OF_BOUNDS, INTEGER_OVERFLOW,
+    FIELD_NOT_FOUND, INTERNAL_ERROR, KEY_NOT_FOUND, MISSING_RETURN_VALUE, NO_MATCHING_ARM, NOT_CALLABLE, TYPE_MISMATCH, UNDECLARED_IDENTIFIER,
+};
+use crate::scope::{Scope, ScopeRef};
+use crate::span::Span;
+use crate::grammar::{StatementBlock, Statement, StatementKind, PrintStatement, Term, TermKind, Expression, ExpressionKind, Value, Operator, UnaryOperator, AssignmentStatement, DeclarationStatement, Iterable, Call, Closure, Operation, InterpolationPart, Index, IndexAssignment, ListRef, Key, Map, MapRef, Field, Instance, StructRef, Variant, Pattern, PatternKind};
+
+// Deep enough for reasonable recursion while staying clear of the interpreter stack limit
+const MAX_CALL_DEPTH: usize = 512;
+// Innermost calls shown for a runtime error, deep recursions being truncated
+const MAX_DISPLAYED_FRAMES: usize = 10;
+// Each call nests several statement and expression frames, unoptimized builds needing more than the main thread offers
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// How the execution of a statement block ended, used to unwind nested
+/// blocks up to the enclosing loop or function call.
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Option<Value>),
+}
+
+/// Function call in progress, with the span of its call site.
+struct CallFrame {
+    function: String,
+    span: Span,
+}
+
+/// Element of a list or entry of a map designated by an index expression.
+enum Element {
+    /// Position within the bounds of the list when the index was evaluated
+    List(ListRef, usize),
+    /// Key of the map, which may not be in it
+    Map(MapRef, Key),
+}
+
+struct Interpreter {
+    call_stack: Vec<CallFrame>,
+    /// Whether integer arithmetic wraps around on overflow instead of failing
+    wrapping: bool,
+}
+
+/// Run the program on a thread of its own, whose stack holds `MAX_CALL_DEPTH`
+/// nested calls whatever the stack of the caller, a failure of the thread
+/// being reported as an internal error.
+pub fn interpret(ast: StatementBlock, wrapping: bool) -> Result<(), Diagnostic> {
+    // Start of the file, for the error of a thread that did not run to its end
+    let span = match ast.statements.first() {
+        Some(statement) => Span::new(statement.span.file.clone(), (0, 0), (0, 0)),
+        None => return Ok(()),
+    };
+    std::thread::Builder::new()
+        .name("interpreter".to_owned())
+        .stack_size(STACK_SIZE)
+        .spawn(move || {
+            let mut interpreter = Interpreter::new(wrapping);
+            interpreter.interpret_statements(&ast.statements, &Scope::new("global scope", None))?;
+            Ok(())
+        })
+        .map_err(|error| format!("cannot start the interpreter thread: {}", error))
+        .and_then(|handle| handle.join().map_err(|panic| panic_message(&panic)))
+        .unwrap_or_else(|cause| {
+            Err(Diagnostic::error(INTERNAL_ERROR, "The interpreter stopped unexpectedly", span)
+                .with_note(&cause)
+                .with_help("this is a bug of the interpreter, please report it along with this file"))
+        })
+}
+
+// Message of a panic, which is a string unless it was raised with another payload
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match panic.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "panicked".to_owned(),
+        },
+    }
+}
+
+// Integers operated with floats are converted to floats, any other pair of
+// values being left as is
+fn promote(left: Value, right: Value) -> (Value, Value) {
+    match (left, right) {
+        (Value::Integer(left), Value::Float(right)) => (Value::Float(left as f64), Value::Float(right)),
+        (Value::Float(left), Value::Integer(right)) => (Value::Float(left), Value::Float(right as f64)),
+        values => values,
+    }
+}
+
+// Whether the value matches the pattern, collecting the values bound by it.
+// Literals match equal values, integers and floats comparing by value.
+fn match_pattern(pattern: &Pattern, value: &Value, bindings: &mut Vec<(String, Value)>) -> bool {
+    match &pattern.kind {
+        PatternKind::Wildcard => true,
+        PatternKind::Binding(identifier) => {
+            bindings.push((identifier.name.clone(), value.clone()));
+            true
+        },
+        PatternKind::Literal(literal) => match (literal, value) {
+            (TermKind::Integer(literal), Value::Integer(value)) => literal == value,
+            (TermKind::Integer(literal), Value::Float(value)) => *literal as f64 == *value,
+            (TermKind::Float(literal), Value::Integer(value)) => *literal == *value as f64,
+            (TermKind::Float(literal), Value::Float(value)) => literal == value,
+            (TermKind::String(literal), Value::String(value)) => literal == value,
+            (TermKind::Bool(literal), Value::Bool(value)) => literal == value,
+            (TermKind::Nil, Value::Nil) => true,
+            _ => false,
+        },
+        PatternKind::Variant(variant_pattern) => match value {
+            Value::Variant(variant) => {
+                variant.enum_name == variant_pattern.enum_name
+                    && variant.name == variant_pattern.variant.name
+                    && variant_pattern.fields.iter().zip(&variant.values).all(|(field, value)| match_pattern(field, value, bindings))
             },
-            Statement::Assignment(assignement) => {
-                let id_name = assignement.identifier.name;
-                let id = Identifier {
-                    name: id_name.clone(),
-                    value: Some(interpret_expression(&var_table, assignement.expression)),
-                };
-                var_table.insert(id_name, id);
+            _ => false,
+        },
+    }
+}
 
+// Name of the called function for messages, anonymous functions having none
+fn callee_name(call: &Call) -> String {
+    match &call.callee.kind {
+        ExpressionKind::Term(Term { kind: TermKind::Identifier(identifier), .. }) => identifier.name.clone(),
+        _ => "<anonymous>".to_owned(),
+    }
+}
+
+impl Interpreter {
+    fn new(wrapping: bool) -> Self {
+        Interpreter {
+            call_stack: vec![],
+            wrapping,
+        }
+    }
+
+    // Error noting the calls in progress, from the innermost one
+    fn get_error(&self, code: &'static str, span: &Span, message: &str) -> Diagnostic {
+        let mut error = Diagnostic::error(code, message, span.clone());
+        for frame in self.call_stack.iter().rev().take(MAX_DISPLAYED_FRAMES) {
+            error = error.with_note(&format!("in function {} called at {}", frame.function, frame.span));
+        }
+        if self.call_stack.len() > MAX_DISPLAYED_FRAMES {
+            error = error.with_note(&format!("... {} more calls", self.call_stack.len() - MAX_DISPLAYED_FRAMES));
+        }
+        error
+    }
+    // Operands labeled with their types
+    fn get_mismatch_error(&self, span: &Span, operation: &Operation, types: (&str, &str)) -> Diagnostic {
+        self.get_error(TYPE_MISMATCH, span, "Cannot operand differents types")
+            .with_secondary(operation.left.span.clone(), types.0)
+            .with_secondary(operation.right.span.clone(), types.1)
+    }
+    // Boolean operand of `&&` or `||`
+    fn check_logical_operand(&self, operand: &Expression, value: Value) -> Result<bool, Diagnostic> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            value => Err(self.get_error(TYPE_MISMATCH, &operand.span, "Logical operators only apply to booleans")
+                .with_label(&format!("expected boolean, found {}", value.type_name()))),
+        }
+    }
+    // Integer from the result of an overflowing operation, which is wrapped
+    // around or reported depending on the overflow mode
+    fn check_overflow(&self, span: &Span, (result, overflowed): (i64, bool)) -> Result<Value, Diagnostic> {
+        if overflowed && !self.wrapping {
+            return Err(self.get_error(INTEGER_OVERFLOW, span, "Integer overflow")
+                .with_label(&format!("result does not fit between {} and {}", i64::MIN, i64::MAX))
+                .with_help("run with `--wrapping` to wrap integer arithmetic around on overflow"));
+        }
+        Ok(Value::Integer(result))
+    }
+
+    fn interpret_block(&mut self, ast: &StatementBlock, scope_name: &str, parent: &ScopeRef<Value>) -> Result<Flow, Diagnostic> {
+        let environment = Scope::new(scope_name, Some(parent.clone()));
+        self.interpret_statements(&ast.statements, &environment)
+    }
+
+    // Evaluate the condition of a branch or a loop
+    fn interpret_condition(&mut self, environment: &ScopeRef<Value>, expression: &Expression, statement: &str) -> Result<bool, Diagnostic> {
+        match self.interpret_expression(environment, expression)? {
+            Value::Bool(b) => Ok(b),
+            value => Err(self.get_error(TYPE_MISMATCH, &expression.span, &format!("{} condition must return a boolean", statement))
+                .with_label(&format!("expected boolean, found {}", value.type_name()))),
+        }
+    }
+
+    fn interpret_statements(&mut self, statements: &[Statement], environment: &ScopeRef<Value>) -> Result<Flow, Diagnostic> {
+        // Functions of the block are declared before running it, so that they
+        // can be called before their declaration and call each other
+        for statement in statements {
+            if let StatementKind::Function(function) = &statement.kind {
+                // Captures the environment it is declared in, so it can call itself
+                let closure = Closure {
+                    parameters: function.parameters.clone(),
+                    statement_block: function.statement_block.clone(),
+                    environment: environment.clone(),
+                };
+                environment.borrow_mut().declare(&function.identifier.name, Value::Function(Rc::new(closure)));
             }
-            Statement::Print(print) => {
-                match print {
-                    PrintStatement::Term(term) => {
-                        match term {
-                            Term::Integer(int) => println!("{}",int),
-                            Term::String(string) => println!("{}",string),
-                            Term::Bool(b) => println!("{}",b),
-                            Term::Identifier(identifier) => {
-                                let id = &var_table.get(&identifier.name).unwrap();
-                                match &id.value {
-                                    Some(value) => println!("{:?}",value),
-                                    None => println!("null"),
-                                }
+        }
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::If(if_statement) => {
+                    let flow = if self.interpret_condition(environment, &if_statement.expression, "If")? {
+                        self.interpret_block(&if_statement.then_statement_block, "if block", environment)?
+                    } else if let Some(block) = &if_statement.else_statement_block {
+                        self.interpret_block(block, "else block", environment)?
+                    } else {
+                        Flow::Normal
+                    };
+                    if !matches!(flow, Flow::Normal) {
+                        return Ok(flow);
+                    }
+                },
+                StatementKind::While(while_statement) => {
+                    while self.interpret_condition(environment, &while_statement.expression, "While")? {
+                        match self.interpret_block(&while_statement.statement_block, "while loop", environment)? {
+                            Flow::Break => break,
+                            Flow::Return(value) => return Ok(Flow::Return(value)),
+                            Flow::Continue | Flow::Normal => continue,
+                        }
+                    }
+                },
+                StatementKind::For(for_statement) => {
+                    // Loop variables are shared by the header and every iteration of the body
+                    let loop_environment = Scope::new("for loop", Some(environment.clone()));
+                    self.interpret_declaration(&loop_environment, &for_statement.initialization)?;
+                    while self.interpret_condition(&loop_environment, &for_statement.condition, "For")? {
+                        match self.interpret_block(&for_statement.statement_block, "for loop", &loop_environment)? {
+                            Flow::Break => break,
+                            Flow::Return(value) => return Ok(Flow::Return(value)),
+                            Flow::Continue | Flow::Normal => {},
+                        }
+                        self.interpret_assignment(&loop_environment, &for_statement.update)?;
+                    }
+                },
+                StatementKind::ForIn(for_in_statement) => {
+                    let values: Box<dyn Iterator<Item = Value>> = match &for_in_statement.iterable {
+                        Iterable::Range(start, end) => {
+                            match (self.interpret_expression(environment, start)?, self.interpret_expression(environment, end)?) {
+                                (Value::Integer(start), Value::Integer(end)) => Box::new((start..end).map(Value::Integer)),
+                                _ => return Err(self.get_error(TYPE_MISMATCH, &start.span.to(&end.span), "Range bounds must be integers")),
                             }
+                        },
+                        // Changes made to the collection by the body do not affect the iteration
+                        Iterable::Collection(collection) => match self.interpret_expression(environment, collection)? {
+                            Value::List(list) => Box::new(list.borrow().clone().into_iter()),
+                            Value::Map(map) => Box::new(map.borrow().keys().map(Key::to_value).collect::<Vec<Value>>().into_iter()),
+                            value => return Err(self.get_error(TYPE_MISMATCH, &collection.span, "Only ranges, lists and maps can be iterated over")
+                                .with_label(&format!("expected list or map, found {}", value.type_name()))),
+                        },
+                    };
+                    for value in values {
+                        // Each iteration gets its own loop variable, closures capturing the current value
+                        let loop_environment = Scope::new("for loop", Some(environment.clone()));
+                        loop_environment.borrow_mut().declare(&for_in_statement.identifier.name, value);
+                        match self.interpret_block(&for_in_statement.statement_block, "for loop", &loop_environment)? {
+                            Flow::Break => break,
+                            Flow::Return(value) => return Ok(Flow::Return(value)),
+                            Flow::Continue | Flow::Normal => {},
                         }
+                    }
+                },
+                StatementKind::Break => return Ok(Flow::Break),
+                StatementKind::Continue => return Ok(Flow::Continue),
+                // Declared when entering the block
+                StatementKind::Function(_) => {},
+                StatementKind::Return(expression) => {
+                    let value = match expression {
+                        Some(expression) => Some(self.interpret_expression(environment, expression)?),
+                        None => None,
+                    };
+                    return Ok(Flow::Return(value));
+                },
+                StatementKind::Expression(expression) => {
+                    // A call used as a statement may not return anything
+                    if let ExpressionKind::Call(call) = &expression.kind {
+                        self.interpret_call(environment, call, &expression.span)?;
+                    } else {
+                        self.interpret_expression(environment, expression)?;
+                    }
+                },
+                StatementKind::Declaration(declaration) => self.interpret_declaration(environment, declaration)?,
+                StatementKind::Assignment(assignement) => self.interpret_assignment(environment, assignement)?,
+                StatementKind::IndexAssignment(assignment) => self.interpret_index_assignment(environment, assignment)?,
+                StatementKind::FieldAssignment(assignment) => {
+                    let (instance, position) = self.interpret_field(environment, &assignment.target)?;
+                    let value = self.interpret_expression(environment, &assignment.expression)?;
+                    instance.borrow_mut().fields[position].1 = value;
+                },
+                // Declarations only matter to the parser checking literals and fields
+                StatementKind::Struct(_) | StatementKind::Enum(_) => {},
+                StatementKind::Delete(index) => match self.interpret_index(environment, index)? {
+                    Element::List(list, position) => {
+                        list.borrow_mut().remove(position);
                     },
-                    PrintStatement::Expression(expression) => {
-                        let value = interpret_expression(&var_table, expression);
-                        match value {
-                            Value::Integer(int) => println!("{}", int),
-                            Value::String(str) => println!("{}", str),
-                            Value::Bool(b) => println!("{}", b),                        
+                    Element::Map(map, key) => {
+                        if map.borrow_mut().remove(&key).is_none() {
+                            return Err(self.get_key_error(&index.index.span, &key));
+                        }
+                    },
+                },
+                StatementKind::Print(print) => {
+                    match print {
+                        PrintStatement::Term(term) => {
+                            match &term.kind {
+                                TermKind::Nil => println!("{}", Value::Nil),
+                                TermKind::Integer(int) => println!("{}",int),
+                                TermKind::Float(float) => println!("{}", Value::Float(*float)),
+                                TermKind::String(string) => println!("{}",string),
+                                TermKind::Bool(b) => println!("{}",b),
+                                TermKind::Identifier(identifier) => {
+                                    match environment.borrow().get(&identifier.name) {
+                                        Some(value) => println!("{}", value),
+                                        None => println!("{}", Value::Nil),
+                                    }
+                                }
+                            }
+                        },
+                        PrintStatement::Expression(expression) => {
+                            let value = self.interpret_expression(environment, expression)?;
+                            println!("{}", value);
                         }
                     }
                 }
             }
         }
+        Ok(Flow::Normal)
+    }
+
+    fn interpret_declaration(&mut self, environment: &ScopeRef<Value>, declaration: &DeclarationStatement) -> Result<(), Diagnostic> {
+        let value = self.interpret_expression(environment, &declaration.expression)?;
+        environment.borrow_mut().declare(&declaration.identifier.name, value);
+        Ok(())
+    }
+
+    fn interpret_assignment(&mut self, environment: &ScopeRef<Value>, assignement: &AssignmentStatement) -> Result<(), Diagnostic> {
+        let value = self.interpret_expression(environment, &assignement.expression)?;
+        if !environment.borrow_mut().assign(&assignement.identifier.name, value) {
+            let message = format!("Identifier {} not declared", assignement.identifier.name);
+            return Err(self.get_error(UNDECLARED_IDENTIFIER, &assignement.identifier.span, &message));
+        }
+        Ok(())
+    }
+
+    fn interpret_index_assignment(&mut self, environment: &ScopeRef<Value>, assignment: &IndexAssignment) -> Result<(), Diagnostic> {
+        let element = self.interpret_index(environment, &assignment.target)?;
+        let value = self.interpret_expression(environment, &assignment.expression)?;
+        match element {
+            Element::List(list, position) => {
+                // The value may have removed elements of the list while being evaluated
+                let length = list.borrow().len();
+                if position >= length {
+                    return Err(self.get_bounds_error(&assignment.target.index.span, position as i64, length));
+                }
+                list.borrow_mut()[position] = value;
+            },
+            // Assigning a missing key adds it to the map
+            Element::Map(map, key) => map.borrow_mut().insert(key, value),
+        }
+        Ok(())
+    }
+
+    // Element designated by an index, checked to be within the bounds of a
+    // list or to be a valid key for a map
+    fn interpret_index(&mut self, context: &ScopeRef<Value>, index: &Index) -> Result<Element, Diagnostic> {
+        let collection = self.interpret_expression(context, &index.collection)?;
+        let value = self.interpret_expression(context, &index.index)?;
+        match collection {
+            Value::List(list) => {
+                let position = match value {
+                    Value::Integer(position) => position,
+                    value => return Err(self.get_error(TYPE_MISMATCH, &index.index.span, "List index must be an integer")
+                        .with_label(&format!("expected integer, found {}", value.type_name()))),
+                };
+                let length = list.borrow().len();
+                match usize::try_from(position) {
+                    Ok(position) if position < length => Ok(Element::List(list, position)),
+                    _ => Err(self.get_bounds_error(&index.index.span, position, length)),
+                }
+            },
+            Value::Map(map) => match Key::from_value(&value) {
+                Some(key) => Ok(Element::Map(map, key)),
+                None => Err(self.get_error(TYPE_MISMATCH, &index.index.span, "Map keys must be strings, integers or booleans")
+                    .with_label(&format!("found {}", value.type_name()))),
+            },
+            value => Err(self.get_error(TYPE_MISMATCH, &index.collection.span, "Only lists and maps can be indexed")
+                .with_label(&format!("expected list or map, found {}", value.type_name()))),
+        }
+    }
+    fn get_bounds_error(&self, span: &Span, position: i64, length: usize) -> Diagnostic {
+        self.get_error(INDEX_OUT_OF_BOUNDS, span, &format!("Index {} out of bounds", position))
+            .with_label(&format!("the length of the list is {}", length))
+            .with_help("indexes start at 0 and must be lower than the length of the list")
+    }
+    // Struct instance and position of the field, checked to be one of its own
+    fn interpret_field(&mut self, context: &ScopeRef<Value>, field: &Field) -> Result<(StructRef, usize), Diagnostic> {
+        let instance = match self.interpret_expression(context, &field.target)? {
+            Value::Struct(instance) => instance,
+            value => return Err(self.get_error(TYPE_MISMATCH, &field.target.span, "Only structs have fields")
+                .with_label(&format!("expected struct, found {}", value.type_name()))),
+        };
+        let position = instance.borrow().position(&field.field.name);
+        match position {
+            Some(position) => Ok((instance, position)),
+            None => {
+                let message = format!("Struct {} has no field {}", instance.borrow().name, field.field.name);
+                Err(self.get_error(FIELD_NOT_FOUND, &field.field.span, &message).with_label("unknown field"))
+            },
+        }
+    }
+    fn get_key_error(&self, span: &Span, key: &Key) -> Diagnostic {
+        self.get_error(KEY_NOT_FOUND, span, &format!("Key {} not found in map", key))
+            .with_label("no entry with this key")
+    }
+
+    fn interpret_call(&mut self, context: &ScopeRef<Value>, call: &Call, span: &Span) -> Result<Option<Value>, Diagnostic> {
+        let function = match self.interpret_expression(context, &call.callee)? {
+            Value::Function(function) => function,
+            value => {
+                let message = format!("Cannot call {}, it is not a function", callee_name(call));
+                return Err(self.get_error(NOT_CALLABLE, span, &message)
+                    .with_secondary(call.callee.span.clone(), &format!("this is a {}", value.type_name())));
+            },
+        };
+        // Arguments are evaluated in the caller environment
+        let mut arguments: Vec<Value> = vec![];
+        for argument in &call.arguments {
+            arguments.push(self.interpret_expression(context, argument)?);
+        }
+        if arguments.len() != function.parameters.len() {
+            let message = format!(
+                "Function {} expects {} arguments but {} were given",
+                callee_name(call),
+                function.parameters.len(),
+                arguments.len()
+            );
+            return Err(self.get_error(ARGUMENT_COUNT, span, &message));
+        }
+        if self.call_stack.len() >= MAX_CALL_DEPTH {
+            return Err(self.get_error(CALL_DEPTH, span, &format!("Maximum call depth of {} exceeded", MAX_CALL_DEPTH)));
+        }
+        let environment = Scope::new(&format!("function {}", callee_name(call)), Some(function.environment.clone()));
+        for (parameter, value) in function.parameters.iter().zip(arguments) {
+            environment.borrow_mut().declare(&parameter.name, value);
+        }
+        self.call_stack.push(CallFrame {
+            function: callee_name(call),
+            span: span.clone(),
+        });
+        let flow = self.interpret_statements(&function.statement_block.statements, &environment)?;
+        self.call_stack.pop();
+        match flow {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(None),
+        }
     }
-}
 
-fn interpret_expression(context: &HashMap<String, Identifier>, expression: Expression) -> Value {
-    match expression {
-        Expression::Operation(op) => {
-            let left = interpret_expression(context, op.left);
-            let right = interpret_expression(context, op.right);
-            match left {
-                Value::Bool(left_b) => {
-                    if let Value::Bool(right_b) = right {
-                        match op.operator {
-                            Operator::Equal => return Value::Bool(left_b == right_b),
-                            Operator::NotEqual => return Value::Bool(left_b != right_b),
-                            Operator::And => return Value::Bool(left_b && right_b),
-                            Operator::Or => return Value::Bool(left_b || right_b),
-                            _ => panic!("Operation not permitted on boolean values")
+    fn interpret_expression(&mut self, context: &ScopeRef<Value>, expression: &Expression) -> Result<Value, Diagnostic> {
+        match &expression.kind {
+            ExpressionKind::Operation(op) => {
+                let left = self.interpret_expression(context, &op.left)?;
+                // The right operand of `??`, `&&` and `||` is only evaluated when needed
+                match op.operator {
+                    Operator::Coalesce => {
+                        return match left {
+                            Value::Nil => self.interpret_expression(context, &op.right),
+                            value => Ok(value),
+                        };
+                    },
+                    Operator::And | Operator::Or => {
+                        let left_b = self.check_logical_operand(&op.left, left)?;
+                        if left_b == matches!(op.operator, Operator::Or) {
+                            return Ok(Value::Bool(left_b));
                         }
-                    } else {
-                        panic!("Cannot operand differents types");
-                    }
+                        let right = self.interpret_expression(context, &op.right)?;
+                        return Ok(Value::Bool(self.check_logical_operand(&op.right, right)?));
+                    },
+                    _ => {},
                 }
-                Value::Integer(left_i) => {
-                    if let Value::Integer(right_i) = right {
-                        match op.operator {
-                            Operator::Equal => return Value::Bool(left_i == right_i),
-                            Operator::NotEqual => return Value::Bool(left_i != right_i),
-                            Operator::Plus => return Value::Integer(left_i + right_i),
-                            Operator::Minus => return Value::Integer(left_i - right_i),
-                            Operator::Division => {
-                                if right_i == 0 {
-                                    panic!("Cannot divide by 0");
-                                }
-                                return Value::Integer(left_i / right_i);
-                            },
-                            Operator::Modulo => return Value::Integer(left_i % right_i),
-                            Operator::Multiplication => return Value::Integer(left_i * right_i),
-                            Operator::Inferior => Value::Bool(left_i < right_i),
-                            Operator::InfOrEqual => Value::Bool(left_i <= right_i),
-                            Operator::Superior => Value::Bool(left_i > right_i),
-                            Operator::SupOrEqual => Value::Bool(left_i >= right_i),
-                            _ => panic!("Operation not permitted on integer values")
+                let right = self.interpret_expression(context, &op.right)?;
+                // Nil is only equal to itself, whatever the other value is
+                if matches!(left, Value::Nil) || matches!(right, Value::Nil) {
+                    let both = matches!((&left, &right), (Value::Nil, Value::Nil));
+                    return match op.operator {
+                        Operator::Equal => Ok(Value::Bool(both)),
+                        Operator::NotEqual => Ok(Value::Bool(!both)),
+                        _ => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Operation not permitted on nil values")
+                            .with_secondary(op.left.span.clone(), left.type_name())
+                            .with_secondary(op.right.span.clone(), right.type_name())
+                            .with_help("use `??` to give a default value to what may be nil")),
+                    };
+                }
+                let types = (left.type_name(), right.type_name());
+                let (left, right) = promote(left, right);
+                match left {
+                    // Operations with nil are already rejected above
+                    Value::Nil => Err(self.get_mismatch_error(&expression.span, op, types)),
+                    Value::Bool(left_b) => {
+                        if let Value::Bool(right_b) = right {
+                            match op.operator {
+                                Operator::Equal => Ok(Value::Bool(left_b == right_b)),
+                                Operator::NotEqual => Ok(Value::Bool(left_b != right_b)),
+                                _ => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Operation not permitted on boolean values")),
+                            }
+                        } else {
+                            Err(self.get_mismatch_error(&expression.span, op, types))
+                        }
+                    }
+                    Value::Integer(left_i) => {
+                        if let Value::Integer(right_i) = right {
+                            match op.operator {
+                                Operator::Equal => Ok(Value::Bool(left_i == right_i)),
+                                Operator::NotEqual => Ok(Value::Bool(left_i != right_i)),
+                                Operator::Plus => self.check_overflow(&expression.span, left_i.overflowing_add(right_i)),
+                                Operator::Minus => self.check_overflow(&expression.span, left_i.overflowing_sub(right_i)),
+                                Operator::Division | Operator::Modulo if right_i == 0 => {
+                                    Err(self.get_error(DIVISION_BY_ZERO, &expression.span, "Cannot divide by 0"))
+                                },
+                                Operator::Division => self.check_overflow(&expression.span, left_i.overflowing_div(right_i)),
+                                Operator::Modulo => self.check_overflow(&expression.span, left_i.overflowing_rem(right_i)),
+                                Operator::Multiplication => self.check_overflow(&expression.span, left_i.overflowing_mul(right_i)),
+                                Operator::Inferior => Ok(Value::Bool(left_i < right_i)),
+                                Operator::InfOrEqual => Ok(Value::Bool(left_i <= right_i)),
+                                Operator::Superior => Ok(Value::Bool(left_i > right_i)),
+                                Operator::SupOrEqual => Ok(Value::Bool(left_i >= right_i)),
+                                _ => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Operation not permitted on integer values")),
+                            }
+                        } else {
+                            Err(self.get_mismatch_error(&expression.span, op, types))
                         }
-                    } else {
-                        panic!("Cannot operand differents types");
                     }
+                    Value::Float(left_f) => {
+                        if let Value::Float(right_f) = right {
+                            match op.operator {
+                                Operator::Equal => Ok(Value::Bool(left_f == right_f)),
+                                Operator::NotEqual => Ok(Value::Bool(left_f != right_f)),
+                                Operator::Plus => Ok(Value::Float(left_f + right_f)),
+                                Operator::Minus => Ok(Value::Float(left_f - right_f)),
+                                Operator::Division | Operator::Modulo if right_f == 0.0 => {
+                                    Err(self.get_error(DIVISION_BY_ZERO, &expression.span, "Cannot divide by 0"))
+                                },
+                                Operator::Division => Ok(Value::Float(left_f / right_f)),
+                                Operator::Modulo => Ok(Value::Float(left_f % right_f)),
+                                Operator::Multiplication => Ok(Value::Float(left_f * right_f)),
+                                Operator::Inferior => Ok(Value::Bool(left_f < right_f)),
+                                Operator::InfOrEqual => Ok(Value::Bool(left_f <= right_f)),
+                                Operator::Superior => Ok(Value::Bool(left_f > right_f)),
+                                Operator::SupOrEqual => Ok(Value::Bool(left_f >= right_f)),
+                                _ => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Operation not permitted on float values")),
+                            }
+                        } else {
+                            Err(self.get_mismatch_error(&expression.span, op, types))
+                        }
+                    }
+                    Value::String(left_s) => {
+                        if let Value::String(right_s) = right {
+                            match op.operator {
+                                Operator::Equal => Ok(Value::Bool(left_s == right_s)),
+                                Operator::NotEqual => Ok(Value::Bool(left_s != right_s)),
+                                Operator::Plus => Ok(Value::String(left_s + &right_s)),
+                                _ => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Operation not permitted on string values")),
+                            }
+                        } else {
+                            Err(self.get_mismatch_error(&expression.span, op, types))
+                        }
+                    },
+                    Value::Function(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Operation not permitted on function values")),
+                    Value::List(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Operation not permitted on list values")),
+                    Value::Map(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Operation not permitted on map values")),
+                    Value::Struct(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Operation not permitted on struct values")),
+                    Value::Variant(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Operation not permitted on enum values")
+                        .with_help("use a match to tell variants apart")),
+                }
+            },
+            ExpressionKind::Unary(unary) => {
+                let operand = self.interpret_expression(context, &unary.operand)?;
+                match unary.operator {
+                    UnaryOperator::Not => match operand {
+                        Value::Nil => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `!` to nil")),
+                        Value::Bool(b) => Ok(Value::Bool(!b)),
+                        Value::Integer(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `!` to an integer value")),
+                        Value::Float(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `!` to a float value")),
+                        Value::String(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `!` to a string value")),
+                        Value::Function(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `!` to a function value")),
+                        Value::List(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `!` to a list value")),
+                        Value::Map(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `!` to a map value")),
+                        Value::Struct(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `!` to a struct value")),
+                        Value::Variant(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `!` to an enum value")),
+                    },
+                    UnaryOperator::Negation => match operand {
+                        Value::Integer(int) => self.check_overflow(&expression.span, int.overflowing_neg()),
+                        Value::Float(float) => Ok(Value::Float(-float)),
+                        Value::Nil => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `-` to nil")),
+                        Value::Bool(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `-` to a boolean value")),
+                        Value::String(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `-` to a string value")),
+                        Value::Function(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `-` to a function value")),
+                        Value::List(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `-` to a list value")),
+                        Value::Map(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `-` to a map value")),
+                        Value::Struct(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `-` to a struct value")),
+                        Value::Variant(_) => Err(self.get_error(TYPE_MISMATCH, &expression.span, "Cannot apply `-` to an enum value")),
+                    },
+                }
+            },
+            ExpressionKind::Call(call) => {
+                match self.interpret_call(context, call, &expression.span)? {
+                    Some(value) => Ok(value),
+                    None => Err(self.get_error(MISSING_RETURN_VALUE, &expression.span, &format!("Function {} does not return a value", callee_name(call)))),
+                }
+            },
+            ExpressionKind::List(elements) => {
+                let mut values: Vec<Value> = vec![];
+                for element in elements {
+                    values.push(self.interpret_expression(context, element)?);
+                }
+                Ok(Value::List(Rc::new(RefCell::new(values))))
+            },
+            ExpressionKind::Map(entries) => {
+                let mut map = Map::default();
+                for (key, value) in entries {
+                    let key_value = self.interpret_expression(context, key)?;
+                    let Some(key_value) = Key::from_value(&key_value) else {
+                        return Err(self.get_error(TYPE_MISMATCH, &key.span, "Map keys must be strings, integers or booleans")
+                            .with_label(&format!("found {}", key_value.type_name())));
+                    };
+                    let value = self.interpret_expression(context, value)?;
+                    map.insert(key_value, value);
+                }
+                Ok(Value::Map(Rc::new(RefCell::new(map))))
+            },
+            ExpressionKind::Struct(literal) => {
+                let mut fields: Vec<(String, Value)> = vec![];
+                for (field, value) in &literal.fields {
+                    fields.push((field.name.clone(), self.interpret_expression(context, value)?));
                 }
-                Value::String(left_s) => {
-                    if let Value::String(right_s) = right {
-                        match op.operator {
-                            Operator::Equal => return Value::Bool(left_s == right_s),
-                            Operator::NotEqual => return Value::Bool(left_s != right_s),
-                            Operator::Plus => return Value::String(left_s + &right_s),
-                            _ => panic!("Operation not permitted on string values")
+                Ok(Value::Struct(Rc::new(RefCell::new(Instance {
+                    name: literal.identifier.name.clone(),
+                    fields,
+                }))))
+            },
+            ExpressionKind::Variant(literal) => {
+                let mut values: Vec<Value> = vec![];
+                for argument in &literal.arguments {
+                    values.push(self.interpret_expression(context, argument)?);
+                }
+                Ok(Value::Variant(Rc::new(Variant {
+                    enum_name: literal.enum_name.name.clone(),
+                    name: literal.variant.name.clone(),
+                    values,
+                })))
+            },
+            ExpressionKind::Match(match_expression) => {
+                let value = self.interpret_expression(context, &match_expression.expression)?;
+                for arm in &match_expression.arms {
+                    let mut bindings: Vec<(String, Value)> = vec![];
+                    if match_pattern(&arm.pattern, &value, &mut bindings) {
+                        let arm_scope = Scope::new("match arm", Some(context.clone()));
+                        for (name, value) in bindings {
+                            arm_scope.borrow_mut().declare(&name, value);
                         }
-                    } else {
-                        panic!("Cannot operand differents types");
+                        return self.interpret_expression(&arm_scope, &arm.expression);
                     }
+                }
+                // Only values of another type than the patterns get here, the
+                // parser checking that the arms cover every value of the type
+                Err(self.get_error(NO_MATCHING_ARM, &match_expression.expression.span, "No arm of the match matches the value")
+                    .with_label(&format!("found {} value {}", value.type_name(), value)))
+            },
+            ExpressionKind::Field(field) => {
+                let (instance, position) = self.interpret_field(context, field)?;
+                let value = instance.borrow().fields[position].1.clone();
+                Ok(value)
+            },
+            ExpressionKind::Index(index) => match self.interpret_index(context, index)? {
+                Element::List(list, position) => Ok(list.borrow()[position].clone()),
+                Element::Map(map, key) => {
+                    let value = map.borrow().get(&key).cloned();
+                    value.ok_or_else(|| self.get_key_error(&index.index.span, &key))
                 },
-            }
-        },
-        Expression::Term(term) => {
-            match term {
-                Term::Integer(int) => return Value::Integer(int),
-                Term::String(string) => return Value::String(string),
-                Term::Bool(b) => return Value::Bool(b),
-                Term::Identifier(id) => {
-                    let scoped_id = context.get(&id.name).unwrap().clone();
-                    return scoped_id.value.unwrap()
-                },
+            },
+            ExpressionKind::Function(function) => Ok(Value::Function(Rc::new(Closure {
+                parameters: function.parameters.clone(),
+                statement_block: function.statement_block.clone(),
+                environment: context.clone(),
+            }))),
+            ExpressionKind::Interpolation(parts) => {
+                let mut string = String::new();
+                for part in parts {
+                    match part {
+                        InterpolationPart::Text(text) => string.push_str(text),
+                        InterpolationPart::Expression(expression) => {
+                            let value = self.interpret_expression(context, expression)?;
+                            string.push_str(&value.to_string());
+                        },
+                    }
+                }
+                Ok(Value::String(string))
+            },
+            ExpressionKind::Term(term) => {
+                match &term.kind {
+                    TermKind::Nil => Ok(Value::Nil),
+                    TermKind::Integer(int) => Ok(Value::Integer(*int)),
+                    TermKind::Float(float) => Ok(Value::Float(*float)),
+                    TermKind::String(string) => Ok(Value::String(string.clone())),
+                    TermKind::Bool(b) => Ok(Value::Bool(*b)),
+                    TermKind::Identifier(id) => {
+                        let value = context.borrow().get(&id.name);
+                        match value {
+                            Some(value) => Ok(value),
+                            None => Err(self.get_error(UNDECLARED_IDENTIFIER, &term.span, &format!("Identifier {} not declared", id.name))),
+                        }
+                    },
+                }
             }
         }
     }
-}
\ No newline at end of file
+}
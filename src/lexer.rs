@@ -1,10 +1,13 @@
-use regex::Regex;
+use std::sync::Arc;
 
-use crate::errors::LexicalError;
+use crate::errors::{Diagnostic, INVALID_ESCAPE, INVALID_TOKEN, UNTERMINATED_COMMENT, UNTERMINATED_STRING};
+use crate::span::{LineIndex, Span};
 
 #[derive(Clone, Debug)]
 pub struct Token {
     pub pos: (i32, i32),
+    /// Position right after the last character of the token
+    pub end: (i32, i32),
     pub token_type: TokenType,
     pub value: String,
 }
@@ -13,111 +16,410 @@ pub enum TokenType {
     Whitespace,
     Keyword,
     GroupDivider,
+    /// `[` and `]`, around list elements and indexes
+    ListDivider,
     StartOfBlock,
     EndOfBlock,
     EndOfStatement,
+    Separator,
+    /// `:` between the key and the value of a map entry
+    Colon,
+    Range,
+    /// `.` before the name of a field or of an enum variant
+    Dot,
+    /// `=>` between the pattern and the expression of a match arm
+    Arrow,
     Logical,
     Numeric,
     Text,
+    /// Text of an interpolated string up to its first `${`
+    TextStart,
+    /// Text of an interpolated string between a `}` and the next `${`
+    TextMiddle,
+    /// Text of an interpolated string from its last `}` to its end
+    TextEnd,
     Identifier,
     Operator,
+    /// Characters no other token matches, already reported by the lexer
+    Error,
 }
 
-impl TokenType {
-    fn regex(&self) -> &'static str {
-        match self {
-            TokenType::Whitespace => "[\\s\\t\\n\\r]",
-            TokenType::Keyword => "(var|if|else|print)",
-            TokenType::GroupDivider => "(\\(|\\))",
-            TokenType::StartOfBlock => "(\\{)",
-            TokenType::EndOfBlock => "(\\})",
-            TokenType::EndOfStatement => "(;)",
-            TokenType::Numeric => "[0-9]+",
-            TokenType::Text => "\'([^\']*)\'",
-            TokenType::Logical => "(true|false)",
-            TokenType::Identifier => "[a-zA-Z_]+[a-zA-Z0-9_]*",
-            TokenType::Operator => "(\\+|\\-|&&|\\|\\||>|<|\\={1,2}|\\!|\\/|\\*|\\%)",
+// Reserved words, only matching whole words so that `variable` stays an identifier
+const KEYWORDS: [&str; 15] = [
+    "var", "if", "else", "print", "while", "for", "break", "continue", "fn", "return", "delete", "struct", "enum", "match",
+    "nil",
+];
+const LOGICALS: [&str; 2] = ["true", "false"];
+// Prefixes of hexadecimal, binary and octal integers
+const RADIX_PREFIXES: [&str; 3] = ["0x", "0b", "0o"];
+// Two characters operators come first so that `>=` is not read as `>` then `=`
+const OPERATORS: [&str; 16] = ["+", "-", "&&", "||", "??", ">=", "<=", "==", "!=", ">", "<", "=", "!", "/", "*", "%"];
+
+/// Token starting the text, with its length and the part of the text making
+/// its value, or `None` when no token starts there.
+fn scan_token(text: &str) -> Option<(TokenType, usize, &str)> {
+    let c = text.chars().next()?;
+    let single = |token_type: TokenType| Some((token_type, 1, &text[..1]));
+    match c {
+        c if c.is_whitespace() => Some((TokenType::Whitespace, c.len_utf8(), &text[..c.len_utf8()])),
+        '(' | ')' => single(TokenType::GroupDivider),
+        '[' | ']' => single(TokenType::ListDivider),
+        '{' => single(TokenType::StartOfBlock),
+        '}' => single(TokenType::EndOfBlock),
+        ';' => single(TokenType::EndOfStatement),
+        ',' => single(TokenType::Separator),
+        ':' => single(TokenType::Colon),
+        '.' if text.starts_with("..") => Some((TokenType::Range, 2, &text[..2])),
+        '.' => single(TokenType::Dot),
+        '=' if text.starts_with("=>") => Some((TokenType::Arrow, 2, &text[..2])),
+        '0'..='9' => {
+            let length = number_length(text);
+            Some((TokenType::Numeric, length, &text[..length]))
+        },
+        'a'..='z' | 'A'..='Z' | '_' => {
+            let length = scan_while(text, |c| c.is_ascii_alphanumeric() || c == '_');
+            let word = &text[..length];
+            Some((classify_word(word), length, word))
+        },
+        _ => OPERATORS
+            .iter()
+            .find(|operator| text.starts_with(*operator))
+            .map(|operator| (TokenType::Operator, operator.len(), &text[..operator.len()])),
+    }
+}
+
+// Words are keywords, booleans or identifiers
+fn classify_word(word: &str) -> TokenType {
+    if KEYWORDS.contains(&word) {
+        TokenType::Keyword
+    } else if LOGICALS.contains(&word) {
+        TokenType::Logical
+    } else {
+        TokenType::Identifier
+    }
+}
+
+// Length of the start of the text made of characters accepted by the predicate
+fn scan_while(text: &str, predicate: impl Fn(char) -> bool) -> usize {
+    text.find(|c| !predicate(c)).unwrap_or(text.len())
+}
+
+// Length of the number starting the text, with an optional fractional part
+// and exponent, a `.` not followed by a digit not being part of it so that
+// `0..10` stays a range. Digits may be separated by `_`. An exponent without
+// digits is kept for the parser to report.
+fn number_length(text: &str) -> usize {
+    let digits = |text: &str| scan_while(text, |c| c.is_ascii_digit() || c == '_');
+    // Digits of hexadecimal, binary and octal integers are checked by the parser
+    if RADIX_PREFIXES.iter().any(|prefix| text.starts_with(prefix)) {
+        return 2 + scan_while(&text[2..], |c| c.is_ascii_alphanumeric() || c == '_');
+    }
+    let mut length = digits(text);
+    let rest = &text[length..];
+    if rest.starts_with('.') && rest[1..].starts_with(|c: char| c.is_ascii_digit()) {
+        length += 1 + digits(&rest[1..]);
+    }
+    let rest = &text[length..];
+    if rest.starts_with(['e', 'E']) {
+        let sign = usize::from(rest[1..].starts_with(['+', '-']));
+        length += 1 + sign + digits(&rest[1 + sign..]);
+    }
+    length
+}
+
+// Length of the nested block comment starting the text, or `None` if it is
+// never closed
+fn block_comment_length(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut offset = 0;
+    while offset < text.len() {
+        let rest = &text[offset..];
+        if rest.starts_with("/*") {
+            depth += 1;
+            offset += 2;
+        } else if rest.starts_with("*/") {
+            depth -= 1;
+            offset += 2;
+            if depth == 0 {
+                return Some(offset);
+            }
+        } else {
+            offset += rest.chars().next().map_or(1, char::len_utf8);
         }
     }
+    None
+}
+
+// Length of the invalid start of the text, running up to the next character
+// starting a valid token
+fn invalid_length(text: &str) -> usize {
+    text.char_indices()
+        .skip(1)
+        .find(|(offset, c)| matches!(c, '\'' | '"') || scan_token(&text[*offset..]).is_some())
+        .map_or(text.len(), |(offset, _)| offset)
+}
+
+// Character and length of the escape sequence starting the text with a
+// backslash, or the length of the invalid sequence
+fn escape_sequence(text: &str) -> Result<(char, usize), usize> {
+    let c = match text[1..].chars().next() {
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('\\') => '\\',
+        Some('\'') => '\'',
+        Some('"') => '"',
+        Some('$') => '$',
+        Some('u') => return unicode_escape(text),
+        other => return Err(1 + other.map_or(0, char::len_utf8)),
+    };
+    Ok((c, 2))
+}
 
-    fn values() -> Vec<TokenType> {
-        vec![
-            TokenType::Whitespace,
-            TokenType::Keyword,
-            TokenType::GroupDivider,
-            TokenType::StartOfBlock,
-            TokenType::EndOfBlock,
-            TokenType::EndOfStatement,
-            TokenType::Numeric,
-            TokenType::Text,
-            TokenType::Logical,
-            TokenType::Identifier,
-            TokenType::Operator,
-        ]
+// Character of the `\u{...}` escape starting the text, the invalid sequence
+// running up to its closing brace when there is one
+fn unicode_escape(text: &str) -> Result<(char, usize), usize> {
+    if !text[2..].starts_with('{') {
+        return Err(2);
     }
+    let digits = scan_while(&text[3..], |c| c.is_ascii_hexdigit());
+    if !text[3 + digits..].starts_with('}') {
+        return Err(3 + digits);
+    }
+    let length = 4 + digits;
+    if digits > 6 {
+        return Err(length);
+    }
+    u32::from_str_radix(&text[3..3 + digits], 16)
+        .ok()
+        .and_then(char::from_u32)
+        .map(|c| (c, length))
+        .ok_or(length)
 }
 
 #[derive(Debug)]
 pub struct LexicalParser {
     tokens: Vec<Token>,
-    source: Vec<String>,
+    source: String,
+    lines: LineIndex,
+    file: Arc<str>,
 }
 impl LexicalParser {
-    pub fn new(source: Vec<String>) -> Self {
+    pub fn new(source: String, file: &str) -> Self {
         Self {
             tokens: vec![],
+            lines: LineIndex::new(&source),
             source,
+            file: Arc::from(file),
         }
     }
-    fn next_token(&mut self, current_line:String, line_position: usize, col_position: usize) -> Result<usize, String> {
-        let next_token = &current_line[col_position..];
-
-        for token_type in TokenType::values().into_iter() {
-            match Regex::new(&format!("^{}", token_type.regex())) {
-                Ok(pattern) => {
-                    let captures = pattern.captures(next_token);
-                    if let Some(captures) = captures {
-                        if !matches!(token_type, TokenType::Whitespace) {
-                            // group(1) is used to get text literal without double quotes
-                            let value = captures
-                                .get(1)
-                                .map_or_else(|| captures.get(0).unwrap().as_str(), |m| m.as_str());
-                            let token = Token {
-                                pos: (line_position as i32, col_position as i32),
-                                token_type,
-                                value: value.to_owned(),
-                            };
-                            self.tokens.push(token);
-                        }
-                        match captures.get(0) {
-                            Some(capture) => return Ok(capture.as_str().len()),
-                            None => return Err("Unknown Token".to_owned()),
-                        }
-                    }
+    /// Split the source in tokens, invalid characters being kept as error
+    /// tokens so that every lexical error of the file is reported.
+    pub fn parse(&mut self) -> (Vec<Token>, Vec<Diagnostic>) {
+        let mut errors: Vec<Diagnostic> = vec![];
+        self.lex_tokens(0, false, &mut errors);
+        (self.tokens.clone(), errors)
+    }
+    // Lex the source from the offset up to its end or, for an expression
+    // embedded in a string, up to its closing brace, and give the offset
+    // where lexing stopped
+    fn lex_tokens(&mut self, mut offset: usize, embedded: bool, errors: &mut Vec<Diagnostic>) -> usize {
+        let mut depth = 0;
+        while offset < self.source.len() {
+            let text = &self.source[offset..];
+            if embedded && text.starts_with('}') {
+                if depth == 0 {
+                    return offset;
                 }
-                Err(_) => return Err("Unknown Token".to_owned()),
+                depth -= 1;
+            } else if embedded && text.starts_with('{') {
+                depth += 1;
             }
-        }
-
-        Err(format!("invalid expression: `{}`", next_token).into())
-    }
-
-    pub fn parse(&mut self) -> Result<Vec<Token>, LexicalError> {
-        for (pos, line) in self.source.clone().iter().enumerate() {
-            let mut col: usize = 0;
-            while col < line.len() {
-                match self.next_token(line.clone(), pos, col) {
-                    Ok(inc) => col += inc,
-                    Err(err) => {
-                        return Err(LexicalError {
-                            line: pos as i32,
-                            col: col as i32,
-                            message: err,
-                        })
+            if text.starts_with("//") {
+                offset += text.find('\n').unwrap_or(text.len());
+                continue;
+            }
+            if text.starts_with("/*") {
+                match block_comment_length(text) {
+                    Some(length) => offset += length,
+                    None => {
+                        errors.push(Diagnostic::error(UNTERMINATED_COMMENT, "Unterminated block comment", self.get_span(offset, offset + 2))
+                            .with_label("comment starting here is never closed")
+                            .with_help("add a `*/` for each `/*` of the comment"));
+                        offset = self.source.len();
                     },
                 }
+                continue;
+            }
+            if text.starts_with("'''") || text.starts_with("\"\"\"") {
+                offset += self.scan_raw_string(offset, errors);
+                continue;
+            }
+            if text.starts_with(['\'', '"']) {
+                offset += self.scan_string(offset, errors);
+                continue;
+            }
+            let (token_type, length, value) = match scan_token(text) {
+                Some(token) => token,
+                None => {
+                    let length = invalid_length(text);
+                    let message = format!("Unknown token `{}`", &text[..length]);
+                    errors.push(Diagnostic::error(INVALID_TOKEN, &message, self.get_span(offset, offset + length)));
+                    (TokenType::Error, length, &text[..length])
+                },
+            };
+            if token_type != TokenType::Whitespace {
+                let value = value.to_owned();
+                self.push_token(token_type, offset, offset + length, value);
             }
+            offset += length;
+        }
+        offset
+    }
+    // Lex the quoted string at the offset and give its length, escape
+    // sequences being decoded into the value of the token. Double quoted
+    // strings are split around the expressions they embed with `${...}`.
+    fn scan_string(&mut self, offset: usize, errors: &mut Vec<Diagnostic>) -> usize {
+        let quote = if self.source[offset..].starts_with('"') { '"' } else { '\'' };
+        let mut value = String::new();
+        // Start of the text part being lexed, after the opening quote or the
+        // brace closing the previous embedded expression
+        let mut part_start = offset;
+        let mut interpolated = false;
+        let mut index = offset + 1;
+        while let Some(c) = self.source[index..].chars().next() {
+            let rest = &self.source[index..];
+            match c {
+                // Only raw strings can span lines
+                '\n' => break,
+                c if c == quote => {
+                    let token_type = if interpolated { TokenType::TextEnd } else { TokenType::Text };
+                    self.push_token(token_type, part_start, index + 1, value);
+                    return index + 1 - offset;
+                },
+                '$' if quote == '"' && rest.starts_with("${") => {
+                    let token_type = if interpolated { TokenType::TextMiddle } else { TokenType::TextStart };
+                    self.push_token(token_type, part_start, index + 2, std::mem::take(&mut value));
+                    let end = self.lex_tokens(index + 2, true, errors);
+                    if end == self.source.len() {
+                        errors.push(Diagnostic::error(UNTERMINATED_STRING, "Unterminated string interpolation", self.get_span(index, index + 2))
+                            .with_label("interpolation starting here is never closed")
+                            .with_help("add a `}` at the end of the embedded expression"));
+                        self.push_token(TokenType::Error, end, end, String::new());
+                        return end - offset;
+                    }
+                    interpolated = true;
+                    part_start = end;
+                    index = end + 1;
+                    continue;
+                },
+                '\\' if matches!(rest[1..].chars().next(), None | Some('\n')) => break,
+                '\\' => {
+                    // Invalid sequences are reported and left out of the value
+                    match escape_sequence(rest) {
+                        Ok((c, length)) => {
+                            value.push(c);
+                            index += length;
+                        },
+                        Err(length) => {
+                            let message = format!("Invalid escape sequence `{}`", &rest[..length]);
+                            errors.push(Diagnostic::error(INVALID_ESCAPE, &message, self.get_span(index, index + length))
+                                .with_label("invalid escape sequence")
+                                .with_help("valid escapes are `\\n`, `\\t`, `\\\\`, `\\'`, `\\\"`, `\\$` and `\\u{...}` with 1 to 6 hexadecimal digits"));
+                            index += length;
+                        },
+                    }
+                    continue;
+                },
+                c => value.push(c),
+            }
+            index += c.len_utf8();
+        }
+        // An unmatched quote opens a string running to the end of the line
+        let end = self.source[index..].find('\n').map_or(self.source.len(), |length| index + length);
+        errors.push(Diagnostic::error(UNTERMINATED_STRING, "Unterminated string literal", self.get_span(part_start, end))
+            .with_label("string starting here is never closed")
+            .with_help(&format!("add a `{}` at the end of the string", quote)));
+        let value = self.source[part_start..end].to_owned();
+        self.push_token(TokenType::Error, part_start, end, value);
+        end - offset
+    }
+    // Lex the triple quoted string at the offset and give its length, its
+    // value being its content as written
+    fn scan_raw_string(&mut self, offset: usize, errors: &mut Vec<Diagnostic>) -> usize {
+        let text = &self.source[offset..];
+        let delimiter = &text[..3];
+        match text[3..].find(delimiter) {
+            Some(end) => {
+                let value = text[3..3 + end].to_owned();
+                self.push_token(TokenType::Text, offset, offset + end + 6, value);
+                end + 6
+            },
+            None => {
+                errors.push(Diagnostic::error(UNTERMINATED_STRING, "Unterminated raw string literal", self.get_span(offset, offset + 3))
+                    .with_label("raw string starting here is never closed")
+                    .with_help(&format!("add a `{}` at the end of the string", delimiter)));
+                let (length, value) = (text.len(), text.to_owned());
+                self.push_token(TokenType::Error, offset, offset + length, value);
+                length
+            },
+        }
+    }
+    fn push_token(&mut self, token_type: TokenType, start: usize, end: usize, value: String) {
+        self.tokens.push(Token {
+            pos: self.lines.position(start),
+            end: self.lines.position(end),
+            token_type,
+            value,
+        });
+    }
+    fn get_span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.file.clone(), self.lines.position(start), self.lines.position(end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tokens of the source, which must lex without errors
+    fn lex(source: &str) -> Vec<Token> {
+        let (tokens, errors) = LexicalParser::new(source.to_owned(), "test.toy").parse();
+        assert!(errors.is_empty(), "errors lexing `{}`", source);
+        tokens
+    }
+
+    #[test]
+    fn words_starting_with_a_keyword_are_identifiers() {
+        let words = [
+            "variable", "iffy", "trueish", "format", "fnord", "returned", "breaking", "continued", "whileX", "printer",
+            "elsewhere", "deleted", "structure", "enumerate", "matches", "nil_value",
+        ];
+        for word in words {
+            let tokens = lex(word);
+            assert_eq!(tokens.len(), 1, "`{}` lexed as {:?}", word, tokens);
+            assert_eq!(tokens[0].token_type, TokenType::Identifier, "`{}`", word);
+            assert_eq!(tokens[0].value, word);
+        }
+    }
+
+    #[test]
+    fn bare_keywords_are_keywords() {
+        let words = KEYWORDS.iter().map(|word| (*word, TokenType::Keyword));
+        for (word, token_type) in words.chain(LOGICALS.iter().map(|word| (*word, TokenType::Logical))) {
+            let tokens = lex(word);
+            assert_eq!(tokens.len(), 1, "`{}` lexed as {:?}", word, tokens);
+            assert_eq!(tokens[0].token_type, token_type, "`{}`", word);
+            assert_eq!(tokens[0].value, word);
+        }
+    }
+
+    #[test]
+    fn exponents_without_digits_stay_in_the_number() {
+        for number in ["1.5e", "2e+", "3E-", "4e_"] {
+            let tokens = lex(number);
+            assert_eq!(tokens.len(), 1, "`{}` lexed as {:?}", number, tokens);
+            assert_eq!(tokens[0].token_type, TokenType::Numeric, "`{}`", number);
+            assert_eq!(tokens[0].value, number);
         }
-        Ok(self.tokens.clone())
     }
 }
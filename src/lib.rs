@@ -0,0 +1,9 @@
+//! Lexer, parser and interpreter of the toy language, shared by the command
+//! line tool and the benchmarks.
+pub mod errors;
+pub mod grammar;
+pub mod interpreter;
+pub mod lexer;
+pub mod parser;
+pub mod scope;
+pub mod span;
@@ -1,14 +1,11 @@
 use std::{
-    fs::{File, metadata},
-    io::{prelude::*, BufReader},
+    fs::{metadata, read_to_string},
+    io::IsTerminal,
 };
-mod grammar;
-mod lexer;
-mod parser;
-mod interpreter;
-mod errors;
+use clap::{Parser, ValueEnum};
 
-use clap::Parser;
+use compiler::errors::{Diagnostic, Severity};
+use compiler::{interpreter, lexer, parser};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -16,49 +13,70 @@ struct Cli {
     /// Run interpreter instead of compiler
     #[arg(short, long)]
     interpreter: bool,
+    /// Wrap integer arithmetic around on overflow instead of stopping with an error
+    #[arg(long)]
+    wrapping: bool,
+    /// When to color diagnostics
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
     /// Path of toy lang file to compile or interpret
     path: Option<String>,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    /// Only when printing to a terminal
+    Auto,
+    Always,
+    Never,
+}
+
 fn main() {
     let cli = Cli::parse();
     let path = cli.path.unwrap();
-    let content: Vec<String>;
-    match metadata(&path) {
+    let file = match metadata(&path) {
         Ok(p) => {
             if p.is_dir() {
-                content = lines_from_file(format!("{path}/index.toy"));
+                format!("{path}/index.toy")
             } else {
-                content = lines_from_file(path);
+                path
             }
         }
         Err(_) => panic!("Error while accessing the file"),
-    }
-    let mut lex = lexer::LexicalParser::new(content);
-    match lex.parse() {
-        Ok(lexicon) => {
-            let mut parser = parser::SyntaxAnalizer::new(lexicon);
-            match parser.parse() {
-                Ok(ast) => {
-                    if cli.interpreter {
-                        interpreter::interpret(ast);
-                    } else {
-                        println!("{:?}", ast);
-                    }
-                },
-                Err(error) => println!("{}", error.to_string()),
-            }
-            
+    };
+    let content = read_to_string(&file).unwrap_or_else(|_| panic!("Compiler is not able to read the file {}", file));
+    let color = match cli.color {
+        ColorChoice::Auto => std::io::stderr().is_terminal(),
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+    };
+    std::process::exit(compile(&file, &content, cli.interpreter, cli.wrapping, color));
+}
+
+// Lex, parse and either print or run the program, returning the exit code
+fn compile(file: &str, content: &str, interpreter: bool, wrapping: bool, color: bool) -> i32 {
+    let report = |diagnostic: &Diagnostic| eprintln!("{}", diagnostic.render(content, color));
+    let mut lex = lexer::LexicalParser::new(content.to_owned(), file);
+    let (lexicon, mut diagnostics) = lex.parse();
+    let mut parser = parser::SyntaxAnalizer::new(lexicon, file);
+    let (ast, syntax_diagnostics) = parser.parse();
+    diagnostics.extend(syntax_diagnostics);
+    diagnostics.iter().for_each(report);
+    let has_errors = diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error);
+    if interpreter {
+        if has_errors {
+            return 1;
+        }
+        if let Err(error) = interpreter::interpret(ast, wrapping) {
+            report(&error);
+            return 1;
+        }
+    } else {
+        // The AST of a program with errors only holds its valid statements
+        println!("{:?}", ast);
+        if has_errors {
+            return 1;
         }
-        Err(error) => println!("{}", error.to_string()),
     }
-    
+    0
 }
-
-fn lines_from_file(filename: String) -> Vec<String> {
-    let file = File::open(filename.clone()).expect(&format!("Compiler is not able to read the file {}", filename));
-    let buf = BufReader::new(file);
-    buf.lines()
-        .map(|l| l.expect("Could not parse line"))
-        .collect()
-}
\ No newline at end of file
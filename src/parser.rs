@@ -1,42 +1,109 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use crate::errors::SyntaxError;
+use crate::errors::{
+    Diagnostic, ALREADY_DECLARED, ARGUMENT_COUNT, ASSIGNMENT_TO_FUNCTION, INVALID_NUMBER,
+    MISPLACED_CONTROL_FLOW, MISSING_FIELDS, NON_EXHAUSTIVE_MATCH, SYNTAX_ERROR, UNDECLARED_IDENTIFIER, UNKNOWN_FIELD,
+    UNREACHABLE_CODE,
+};
 use crate::grammar::{
-    AssignmentStatement, Expression, Identifier, IfStatement, Operation, Operator, PrintStatement,
-    Statement, StatementBlock, Term,
+    AssignmentStatement, Call, DeclarationStatement, EnumDeclaration, Expression, ExpressionKind, Field,
+    FieldAssignment, ForInStatement, ForStatement, FunctionDeclaration, FunctionLiteral, Identifier, IfStatement, Index,
+    IndexAssignment, InterpolationPart, Iterable, Match, MatchArm, Operation, Operator, Pattern, PatternKind, PrintStatement, Statement, StatementBlock, StatementKind,
+    StructDeclaration, StructLiteral, Term, TermKind, Unary, UnaryOperator, VariantDeclaration,
+    VariantLiteral, VariantPattern, WhileStatement,
 };
 use crate::lexer::{Token, TokenType};
+use crate::scope::{Scope, ScopeRef};
+use crate::span::Span;
+
+/// Type declared by the program, used to check the literals and fields
+/// referring to it.
+#[derive(Debug, Clone)]
+enum TypeDefinition {
+    Struct(StructDeclaration),
+    Enum(EnumDeclaration),
+}
+impl TypeDefinition {
+    fn identifier(&self) -> &Identifier {
+        match self {
+            TypeDefinition::Struct(declaration) => &declaration.identifier,
+            TypeDefinition::Enum(declaration) => &declaration.identifier,
+        }
+    }
+}
+
+/// What an identifier refers to, arity of declared functions being known
+/// statically, along with where it was declared.
+#[derive(Debug, Clone)]
+enum Symbol {
+    Variable(Span),
+    Function(usize, Span),
+}
+impl Symbol {
+    fn span(&self) -> &Span {
+        match self {
+            Symbol::Variable(span) | Symbol::Function(_, span) => span,
+        }
+    }
+}
+
 pub struct SyntaxAnalizer {
     tokens: Vec<Token>,
-    pub ast: StatementBlock,
     current_token: Option<Token>,
     peek_token: Option<Token>,
     token_pos: usize,
     file_pos: (i32, i32),
+    // End of the last consumed token, closing the span of the node being parsed
+    previous_end: (i32, i32),
+    file: Arc<str>,
+    loop_depth: usize,
+    function_depth: usize,
+    // Map and struct literals being parsed, whose braces are not blocks
+    open_literals: usize,
+    scope: ScopeRef<Symbol>,
+    // Types declared so far, by name, whatever the scope they are declared in
+    types: HashMap<String, TypeDefinition>,
+    // Errors recovered from and warnings, in the order they were found
+    diagnostics: Vec<Diagnostic>,
 }
 impl SyntaxAnalizer {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<Token>, file: &str) -> Self {
         let mut analizer = SyntaxAnalizer {
             tokens: tokens.clone(),
-            ast: StatementBlock {
-                statements: vec![],
-                symbol_table: HashMap::new(),
-            },
             current_token: None,
             peek_token: None,
             token_pos: 1,
-            file_pos: (1,1),
+            file_pos: (0, 0),
+            previous_end: (0, 0),
+            file: Arc::from(file),
+            loop_depth: 0,
+            function_depth: 0,
+            open_literals: 0,
+            scope: Scope::new("global scope", None),
+            types: HashMap::new(),
+            diagnostics: vec![],
         };
-        if tokens.len() > 2 {
-            analizer.current_token = Some(tokens[0].clone());
-            analizer.peek_token = Some(tokens[1].clone());
-        } else {
-            panic!("Empty file");
+        // Files too short to hold a block are reported when parsing them
+        analizer.current_token = tokens.first().cloned();
+        analizer.peek_token = tokens.get(1).cloned();
+        if let Some(token) = &analizer.current_token {
+            analizer.file_pos = token.pos;
         }
         analizer
     }
-    pub fn parse(&mut self) -> Result<StatementBlock, SyntaxError>{
-        self.parse_statement_block()
+    /// Parse the whole program, skipping statements with syntax errors so
+    /// that every error is reported and the rest of the program is still
+    /// returned.
+    pub fn parse(&mut self) -> (StatementBlock, Vec<Diagnostic>) {
+        let block = match self.parse_statements() {
+            Ok(block) => block,
+            Err(error) => {
+                self.diagnostics.push(error);
+                StatementBlock { statements: vec![] }
+            }
+        };
+        (block, std::mem::take(&mut self.diagnostics))
     }
     fn check_token(&mut self, token_type: TokenType) -> bool {
         match self.current_token.clone() {
@@ -44,21 +111,21 @@ impl SyntaxAnalizer {
             None => false,
         }
     }
-    fn check_peek(&mut self, token_type: TokenType) -> bool {
-        match self.peek_token.clone() {
-            Some(token) => token.token_type == token_type,
+    fn check_token_and_value(&mut self, token_type: TokenType, value: &str) -> bool {
+        match self.current_token.clone() {
+            Some(token) => token.token_type == token_type && token.value == value,
             None => false,
         }
     }
-    fn check_token_and_value(&mut self, token_type: TokenType, value: &str) -> bool {
-        match self.current_token.clone() {
-            Some(token) => token.token_type == token_type && token.value == value.to_string(),
+    fn check_peek(&mut self, token_type: TokenType) -> bool {
+        match self.peek_token.clone() {
+            Some(token) => token.token_type == token_type,
             None => false,
         }
     }
     fn check_peek_and_value(&mut self, token_type: TokenType, value: &str) -> bool {
         match self.peek_token.clone() {
-            Some(token) => token.token_type == token_type && token.value == value.to_string(),
+            Some(token) => token.token_type == token_type && token.value == value,
             None => false,
         }
     }
@@ -69,6 +136,9 @@ impl SyntaxAnalizer {
         }
     }
     fn next_token(&mut self) {
+        if let Some(token) = &self.current_token {
+            self.previous_end = token.end;
+        }
         self.token_pos += 1;
         self.current_token = self.peek_token.clone();
         if let Some(token) = self.current_token.clone() {
@@ -80,265 +150,1614 @@ impl SyntaxAnalizer {
             self.peek_token = None;
         }
     }
-    fn parse_statement_block(&mut self) -> Result<StatementBlock, SyntaxError> {
+    // Parse a block in a new scope nested in the current one
+    fn parse_statement_block(&mut self, scope_name: &str) -> Result<StatementBlock, Diagnostic> {
+        let scope = Scope::new(scope_name, Some(self.scope.clone()));
+        self.parse_statement_block_in(scope)
+    }
+    // Parse a block in a scope already holding symbols declared outside of
+    // its braces, like parameters
+    fn parse_statement_block_in(&mut self, scope: ScopeRef<Symbol>) -> Result<StatementBlock, Diagnostic> {
+        let enclosing_scope = std::mem::replace(&mut self.scope, scope);
+        let block = self.parse_statements();
+        self.scope = enclosing_scope;
+        block
+    }
+    fn parse_statements(&mut self) -> Result<StatementBlock, Diagnostic> {
         let mut block = StatementBlock {
             statements: vec![],
-            symbol_table: HashMap::new(),
         };
-        if self.check_token(TokenType::StartOfBlock) {
-            self.next_token();
-            while !self.check_token(TokenType::EndOfBlock) {
-                match self.parse_statement(&mut block){
-                    Ok(statement) => block.statements.push(statement),
-                    Err(error) => return Err(error),
+        if !self.check_token(TokenType::StartOfBlock) {
+            return Err(self.get_error("Missing opening block"));
+        }
+        self.next_token();
+        self.declare_functions();
+        // Statement after which the rest of the block never runs
+        let mut exit: Option<Span> = None;
+        let mut unreachable_reported = false;
+        while !self.check_token(TokenType::EndOfBlock) {
+            if self.current_token.is_none() {
+                let error = self.get_error("Missing closing block");
+                self.diagnostics.push(error);
+                return Ok(block);
+            }
+            let open_literals = self.open_literals;
+            let statement = match self.parse_statement() {
+                Ok(statement) => statement,
+                Err(error) => {
+                    // Errors caused by an invalid token were already reported by the lexer
+                    if !self.check_token(TokenType::Error) {
+                        self.diagnostics.push(error);
+                    }
+                    let unclosed_literals = std::mem::replace(&mut self.open_literals, open_literals) - open_literals;
+                    self.synchronize(unclosed_literals);
+                    continue;
+                }
+            };
+            if let Some(exit) = &exit {
+                if !unreachable_reported {
+                    let warning = Diagnostic::warning(UNREACHABLE_CODE, "Unreachable statement", statement.span.clone())
+                        .with_label("unreachable statement")
+                        .with_secondary(exit.clone(), "any code following this statement is unreachable");
+                    self.diagnostics.push(warning);
+                    unreachable_reported = true;
                 }
+            } else if matches!(statement.kind, StatementKind::Return(_) | StatementKind::Break | StatementKind::Continue) {
+                exit = Some(statement.span.clone());
             }
-            self.next_token();
-        } else {
-            return Err(self.get_error("Missing opening block"));
+            block.statements.push(statement);
         }
-        return Ok(block);
+        self.next_token();
+        Ok(block)
     }
-    fn parse_statement(&mut self, block: &mut StatementBlock) -> Result<Statement, SyntaxError> {
-        // print_statement ::= (expression) | string_literal
-        if self.check_token_and_value(TokenType::Keyword, "print") {
-            self.next_token();
-            // Should start with brackets
-            if self.check_token_and_value(TokenType::GroupDivider, "(") {
-                self.next_token();
-                // If there only string, save print statement
-                if self.check_token(TokenType::Text)
-                    && self.check_peek_and_value(TokenType::GroupDivider, ")")
-                {
-                    let text = self.current_token.clone();
+    // Skip tokens up to the end of the statement in error, which is either
+    // after its `;`, after a block it opened, or before the `}` closing the
+    // enclosing block. Braces of the map and struct literals the statement
+    // was in the middle of are skipped on the way.
+    fn synchronize(&mut self, mut unclosed_literals: usize) {
+        let mut depth = 0;
+        while let Some(token) = self.current_token.clone() {
+            match token.token_type {
+                TokenType::EndOfStatement if depth == 0 => {
                     self.next_token();
-                    self.next_token();
-                    if self.check_token(TokenType::EndOfStatement) {
+                    return;
+                },
+                TokenType::StartOfBlock => depth += 1,
+                TokenType::EndOfBlock if depth == 0 && unclosed_literals > 0 => unclosed_literals -= 1,
+                TokenType::EndOfBlock if depth == 0 => return,
+                TokenType::EndOfBlock => {
+                    depth -= 1;
+                    if depth == 0 {
                         self.next_token();
-                        return Ok(Statement::Print(PrintStatement::Term(Term::String(
-                            self.get_token_value(text),
-                        ))));
-                    } else {
-                        return Err(self.get_error("Missing end of statement"));
-                    }
-                } else {
-                    // It's an expression
-                    let expression: Expression;
-                    match self.parse_expression(block) {
-                        Ok(exp) => expression = exp,
-                        Err(error) => return Err(error),
+                        return;
                     }
-                    // Check for closing bracket
-                    if self.check_token_and_value(TokenType::GroupDivider, ")") {
-                        self.next_token();
-                        if self.check_token(TokenType::EndOfStatement) {
-                            self.next_token();
-                            return Ok(Statement::Print(PrintStatement::Expression(expression)));
-                        } else {
-                            return Err(self.get_error("Missing end of statement"));
+                },
+                _ => {},
+            }
+            self.next_token();
+        }
+    }
+    // Declare the functions of the block starting at the current token before
+    // parsing its statements, so that they can call functions declared after
+    // them. Names already taken are left for the declaration to report.
+    fn declare_functions(&mut self) {
+        let mut depth = 0;
+        let mut position = self.token_pos - 1;
+        while let Some(token) = self.tokens.get(position) {
+            match token.token_type {
+                TokenType::StartOfBlock => depth += 1,
+                TokenType::EndOfBlock if depth == 0 => return,
+                TokenType::EndOfBlock => depth -= 1,
+                TokenType::Keyword if depth == 0 && token.value == "fn" => {
+                    if let Some((name, symbol)) = self.function_signature(position + 1) {
+                        if !self.scope.borrow().is_declared_locally(&name) {
+                            self.scope.borrow_mut().declare(&name, symbol);
                         }
-                    } else {
-                        return Err(self.get_error("Missing closing bracket"));
                     }
-                }
-            } else {
-                return Err(self.get_error("Missing opening bracket"));
+                },
+                _ => {},
+            }
+            position += 1;
+        }
+    }
+    // Name and symbol of the function declared from the token at the position
+    // on, when it is followed by a valid parameter list
+    fn function_signature(&self, position: usize) -> Option<(String, Symbol)> {
+        let identifier = self.tokens.get(position).filter(|token| token.token_type == TokenType::Identifier)?;
+        let opening = self.tokens.get(position + 1)?;
+        if opening.token_type != TokenType::GroupDivider || opening.value != "(" {
+            return None;
+        }
+        let mut arity = 0;
+        for token in &self.tokens[position + 2..] {
+            match token.token_type {
+                TokenType::Identifier => arity += 1,
+                TokenType::Separator => {},
+                TokenType::GroupDivider if token.value == ")" => {
+                    let span = Span::new(self.file.clone(), identifier.pos, identifier.end);
+                    return Some((identifier.value.clone(), Symbol::Function(arity, span)));
+                },
+                _ => return None,
             }
-        // if_statement ::= if (expression) statement_block else statement_block
+        }
+        None
+    }
+    // Declare a symbol in the current scope, identifiers can shadow the ones
+    // of enclosing scopes but not be declared twice in the same scope
+    fn declare(&mut self, name: &str, symbol: Symbol) -> Result<(), Diagnostic> {
+        let scope = self.scope.clone();
+        let scope_name = scope.borrow().name().to_owned();
+        declare_in(&scope, name, symbol, &format!("Identifier {} already declared in {}", name, scope_name))
+    }
+    fn resolve(&mut self, identifier: &Identifier) -> Result<Symbol, Diagnostic> {
+        let symbol = self.scope.borrow().get(&identifier.name);
+        match symbol {
+            Some(symbol) => Ok(symbol),
+            None => {
+                let scope_name = self.scope.borrow().name().to_owned();
+                let message = format!("Identifier {} not declared in {}", identifier.name, scope_name);
+                // Resolved right after the identifier, a call being followed by its arguments
+                let help = if self.check_token_and_value(TokenType::GroupDivider, "(") {
+                    format!("declare it with `fn {}(...) {{ ... }}` in this block or an enclosing one", identifier.name)
+                } else {
+                    format!("declare it first with `var {} = ...;`", identifier.name)
+                };
+                Err(Diagnostic::error(UNDECLARED_IDENTIFIER, &message, identifier.span.clone())
+                    .with_label("not found in this scope")
+                    .with_help(&help))
+            }
+        }
+    }
+    fn parse_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.file_pos;
+        let kind = if self.check_token_and_value(TokenType::Keyword, "print") {
+            self.parse_print_statement()
         } else if self.check_token_and_value(TokenType::Keyword, "if") {
-            let expression: Expression;
-            let then_statement_block: StatementBlock;
-            let mut else_statement_block: Option<StatementBlock> = None;
+            self.parse_if_statement()
+        } else if self.check_token_and_value(TokenType::Keyword, "while") {
+            self.parse_while_statement()
+        } else if self.check_token_and_value(TokenType::Keyword, "for") {
+            if self.check_peek_and_value(TokenType::GroupDivider, "(") {
+                self.parse_for_statement()
+            } else {
+                self.parse_for_in_statement()
+            }
+        } else if self.check_token_and_value(TokenType::Keyword, "break") {
+            self.parse_loop_control_statement(StatementKind::Break, "break")
+        } else if self.check_token_and_value(TokenType::Keyword, "continue") {
+            self.parse_loop_control_statement(StatementKind::Continue, "continue")
+        } else if self.check_token_and_value(TokenType::Keyword, "fn") {
+            self.parse_function_declaration()
+        } else if self.check_token_and_value(TokenType::Keyword, "return") {
+            self.parse_return_statement()
+        } else if self.check_token_and_value(TokenType::Keyword, "var") {
+            self.parse_declaration_statement()
+        } else if self.check_token_and_value(TokenType::Keyword, "delete") {
+            self.parse_delete_statement()
+        } else if self.check_token_and_value(TokenType::Keyword, "struct") {
+            self.parse_struct_declaration()
+        } else if self.check_token_and_value(TokenType::Keyword, "enum") {
+            self.parse_enum_declaration()
+        } else if self.check_token(TokenType::Identifier)
+            && (self.check_peek_and_value(TokenType::GroupDivider, "(")
+                || self.check_peek_and_value(TokenType::ListDivider, "[")
+                || self.check_peek_and_value(TokenType::Dot, "."))
+        {
+            self.parse_expression_statement()
+        } else if self.check_token(TokenType::Identifier) {
+            self.parse_assignment_statement()
+        } else {
+            Err(self.get_error(&format!("Expected a statement, found {}", self.describe_token())))
+        }?;
+        Ok(Statement {
+            span: self.get_span(start),
+            kind,
+        })
+    }
+    // print_statement ::= (expression) | string_literal
+    fn parse_print_statement(&mut self) -> Result<StatementKind, Diagnostic> {
+        self.next_token();
+        // Should start with brackets
+        if !self.check_token_and_value(TokenType::GroupDivider, "(") {
+            return Err(self.get_error("Missing opening bracket"));
+        }
+        self.next_token();
+        // If there only string, save print statement
+        let statement = if self.check_token(TokenType::Text)
+            && self.check_peek_and_value(TokenType::GroupDivider, ")")
+        {
+            StatementKind::Print(PrintStatement::Term(self.parse_term()?))
+        } else {
+            // It's an expression
+            let expression = self.parse_expression()?;
+            StatementKind::Print(PrintStatement::Expression(expression))
+        };
+        // Check for closing bracket
+        if !self.check_token_and_value(TokenType::GroupDivider, ")") {
+            return Err(self.get_error("Missing closing bracket"));
+        }
+        self.next_token();
+        self.parse_end_of_statement()?;
+        Ok(statement)
+    }
+    // if_statement ::= if (expression) statement_block else statement_block
+    fn parse_if_statement(&mut self) -> Result<StatementKind, Diagnostic> {
+        self.next_token();
+        // Should start with brackets
+        if !self.check_token_and_value(TokenType::GroupDivider, "(") {
+            return Err(self.get_error("Missing opening bracket"));
+        }
+        self.next_token();
+        let expression = self.parse_expression()?;
+        // Check for closing bracket
+        if !self.check_token_and_value(TokenType::GroupDivider, ")") {
+            return Err(self.get_error("Missing closing bracket"));
+        }
+        self.next_token();
+        let then_statement_block = self.parse_statement_block("if block")?;
+        let mut else_statement_block: Option<StatementBlock> = None;
+        if self.check_token_and_value(TokenType::Keyword, "else") {
             self.next_token();
-            // Should start with brackets
-            if self.check_token_and_value(TokenType::GroupDivider, "(") {
+            else_statement_block = Some(self.parse_statement_block("else block")?);
+        }
+        Ok(StatementKind::If(IfStatement {
+            expression,
+            then_statement_block,
+            else_statement_block,
+        }))
+    }
+    // while_statement ::= while (expression) statement_block
+    fn parse_while_statement(&mut self) -> Result<StatementKind, Diagnostic> {
+        self.next_token();
+        // Should start with brackets
+        if !self.check_token_and_value(TokenType::GroupDivider, "(") {
+            return Err(self.get_error("Missing opening bracket"));
+        }
+        self.next_token();
+        let expression = self.parse_expression()?;
+        // Check for closing bracket
+        if !self.check_token_and_value(TokenType::GroupDivider, ")") {
+            return Err(self.get_error("Missing closing bracket"));
+        }
+        self.next_token();
+        let statement_block = self.parse_loop_body("while loop")?;
+        Ok(StatementKind::While(WhileStatement {
+            expression,
+            statement_block,
+        }))
+    }
+    // for_statement ::= for (declaration_statement; expression; assignment_statement) statement_block
+    fn parse_for_statement(&mut self) -> Result<StatementKind, Diagnostic> {
+        self.next_token();
+        self.next_token();
+        if !self.check_token_and_value(TokenType::Keyword, "var") {
+            return Err(self.get_error("Loop variable declaration needed in for statement"));
+        }
+        // The loop variable lives in its own scope, enclosing the body
+        let loop_scope = Scope::new("for loop", Some(self.scope.clone()));
+        let enclosing_scope = std::mem::replace(&mut self.scope, loop_scope);
+        let for_statement = self.parse_for_header_and_body();
+        self.scope = enclosing_scope;
+        for_statement
+    }
+    fn parse_for_header_and_body(&mut self) -> Result<StatementKind, Diagnostic> {
+        let initialization = self.parse_declaration()?;
+        self.parse_end_of_statement()?;
+        let condition = self.parse_expression()?;
+        self.parse_end_of_statement()?;
+        let update = self.parse_assignment()?;
+        // Check for closing bracket
+        if !self.check_token_and_value(TokenType::GroupDivider, ")") {
+            return Err(self.get_error("Missing closing bracket"));
+        }
+        self.next_token();
+        let statement_block = self.parse_loop_body("for loop")?;
+        Ok(StatementKind::For(ForStatement {
+            initialization,
+            condition,
+            update,
+            statement_block,
+        }))
+    }
+    // for_in_statement ::= for identifier in expression..expression statement_block
+    //                    | for identifier in expression statement_block
+    fn parse_for_in_statement(&mut self) -> Result<StatementKind, Diagnostic> {
+        self.next_token();
+        if !self.check_token(TokenType::Identifier) {
+            return Err(self.get_error("Identifier needed after for keyword"));
+        }
+        let identifier = self.parse_identifier();
+        if !self.check_token_and_value(TokenType::Identifier, "in") {
+            return Err(self.get_error("Missing in keyword after loop variable"));
+        }
+        self.next_token();
+        let start = self.parse_expression()?;
+        // Any expression not followed by `..` is a collection
+        let iterable = if self.check_token(TokenType::Range) {
+            self.next_token();
+            Iterable::Range(start, self.parse_expression()?)
+        } else {
+            Iterable::Collection(start)
+        };
+        // The loop variable lives in its own scope, enclosing the body
+        let loop_scope = Scope::new("for loop", Some(self.scope.clone()));
+        loop_scope.borrow_mut().declare(&identifier.name, Symbol::Variable(identifier.span.clone()));
+        let enclosing_scope = std::mem::replace(&mut self.scope, loop_scope);
+        let statement_block = self.parse_loop_body("for loop");
+        self.scope = enclosing_scope;
+        let statement_block = statement_block?;
+        Ok(StatementKind::ForIn(ForInStatement {
+            identifier,
+            iterable,
+            statement_block,
+        }))
+    }
+    fn parse_loop_body(&mut self, scope_name: &str) -> Result<StatementBlock, Diagnostic> {
+        self.loop_depth += 1;
+        let statement_block = self.parse_statement_block(scope_name);
+        self.loop_depth -= 1;
+        statement_block
+    }
+    // break_statement ::= break
+    // continue_statement ::= continue
+    fn parse_loop_control_statement(&mut self, statement: StatementKind, keyword: &str) -> Result<StatementKind, Diagnostic> {
+        if self.loop_depth == 0 {
+            let message = format!("Cannot use {} outside of a loop", keyword);
+            return Err(Diagnostic::error(MISPLACED_CONTROL_FLOW, &message, self.get_token_span())
+                .with_label(&format!("{} outside of a loop", keyword)));
+        }
+        self.next_token();
+        self.parse_end_of_statement()?;
+        Ok(statement)
+    }
+    // function_declaration ::= fn identifier (parameters) statement_block
+    fn parse_function_declaration(&mut self) -> Result<StatementKind, Diagnostic> {
+        self.next_token();
+        if !self.check_token(TokenType::Identifier) {
+            return Err(self.get_error("Identifier needed after fn keyword"));
+        }
+        let identifier = self.parse_identifier();
+        let function_scope = Scope::new(&format!("function {}", identifier.name), Some(self.scope.clone()));
+        let parameters = self.parse_parameters(&function_scope)?;
+        // Usually declared with the other functions of the block already,
+        // and otherwise before the body so the function can call itself
+        let declared = matches!(self.scope.borrow().get(&identifier.name), Some(Symbol::Function(_, span)) if span == identifier.span);
+        if !declared {
+            self.declare(&identifier.name, Symbol::Function(parameters.len(), identifier.span.clone()))?;
+        }
+        let statement_block = self.parse_function_body(function_scope)?;
+        Ok(StatementKind::Function(FunctionDeclaration {
+            identifier,
+            parameters,
+            statement_block,
+        }))
+    }
+    // function_literal ::= fn (parameters) statement_block
+    fn parse_function_literal(&mut self) -> Result<ExpressionKind, Diagnostic> {
+        self.next_token();
+        let function_scope = Scope::new("anonymous function", Some(self.scope.clone()));
+        let parameters = self.parse_parameters(&function_scope)?;
+        let statement_block = self.parse_function_body(function_scope)?;
+        Ok(ExpressionKind::Function(Box::new(FunctionLiteral {
+            parameters,
+            statement_block,
+        })))
+    }
+    // parameters ::= identifier | parameters , identifier
+    fn parse_parameters(&mut self, function_scope: &ScopeRef<Symbol>) -> Result<Vec<Identifier>, Diagnostic> {
+        if !self.check_token_and_value(TokenType::GroupDivider, "(") {
+            return Err(self.get_error("Missing opening bracket"));
+        }
+        self.next_token();
+        let mut parameters: Vec<Identifier> = vec![];
+        while !self.check_token_and_value(TokenType::GroupDivider, ")") {
+            if !parameters.is_empty() {
+                if !self.check_token(TokenType::Separator) {
+                    return Err(self.get_error("Missing ',' between parameters"));
+                }
                 self.next_token();
-                match self.parse_expression(block){
-                    Ok(exp) => expression = exp,
-                    Err(error) => return Err(error),
+            }
+            if !self.check_token(TokenType::Identifier) {
+                return Err(self.get_error("Identifier needed as parameter"));
+            }
+            let parameter = self.parse_identifier();
+            let message = format!("Parameter {} already used", parameter.name);
+            declare_in(function_scope, &parameter.name, Symbol::Variable(parameter.span.clone()), &message)?;
+            parameters.push(parameter);
+        }
+        self.next_token();
+        Ok(parameters)
+    }
+    fn parse_function_body(&mut self, function_scope: ScopeRef<Symbol>) -> Result<StatementBlock, Diagnostic> {
+        // Loops enclosing the function cannot be broken from the body
+        let loop_depth = self.loop_depth;
+        self.loop_depth = 0;
+        self.function_depth += 1;
+        let statement_block = self.parse_statement_block_in(function_scope);
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+        statement_block
+    }
+    // return_statement ::= return | return expression
+    fn parse_return_statement(&mut self) -> Result<StatementKind, Diagnostic> {
+        if self.function_depth == 0 {
+            return Err(Diagnostic::error(MISPLACED_CONTROL_FLOW, "Cannot use return outside of a function", self.get_token_span())
+                .with_label("return outside of a function"));
+        }
+        self.next_token();
+        let mut expression: Option<Expression> = None;
+        if !self.check_token(TokenType::EndOfStatement) {
+            expression = Some(self.parse_expression()?);
+        }
+        self.parse_end_of_statement()?;
+        Ok(StatementKind::Return(expression))
+    }
+    // expression_statement ::= expression
+    // index_assignment ::= expression[expression] = expression
+    // field_assignment ::= expression.identifier = expression
+    fn parse_expression_statement(&mut self) -> Result<StatementKind, Diagnostic> {
+        let expression = self.parse_expression()?;
+        if !self.check_token_and_value(TokenType::Operator, "=") {
+            self.parse_end_of_statement()?;
+            return Ok(StatementKind::Expression(expression));
+        }
+        let statement = match expression.kind {
+            ExpressionKind::Index(target) => StatementKind::IndexAssignment(IndexAssignment {
+                target: *target,
+                expression: self.parse_assigned_expression()?,
+            }),
+            ExpressionKind::Field(target) => StatementKind::FieldAssignment(FieldAssignment {
+                target: *target,
+                expression: self.parse_assigned_expression()?,
+            }),
+            _ => {
+                return Err(Diagnostic::error(SYNTAX_ERROR, "Invalid left-hand side of assignment", expression.span)
+                    .with_label("cannot assign to this expression"))
+            },
+        };
+        self.parse_end_of_statement()?;
+        Ok(statement)
+    }
+    // struct_declaration ::= struct identifier { fields }
+    // fields ::= identifier | fields , identifier
+    fn parse_struct_declaration(&mut self) -> Result<StatementKind, Diagnostic> {
+        self.next_token();
+        if !self.check_token(TokenType::Identifier) {
+            return Err(self.get_error("Identifier needed after struct keyword"));
+        }
+        let identifier = self.parse_identifier();
+        if !self.check_token(TokenType::StartOfBlock) {
+            return Err(self.get_error("Missing opening block"));
+        }
+        self.next_token();
+        let mut fields: Vec<Identifier> = vec![];
+        while !self.check_token(TokenType::EndOfBlock) {
+            if self.current_token.is_none() {
+                return Err(self.get_error("Missing closing block"));
+            }
+            if let Err(error) = self.parse_struct_field(&identifier, &mut fields) {
+                if !self.check_token(TokenType::Error) {
+                    self.diagnostics.push(error);
                 }
-                // Check for closing bracket
-                if self.check_token_and_value(TokenType::GroupDivider, ")") {
+                // The struct is declared with the fields before the error
+                while self.current_token.is_some() && !self.check_token(TokenType::EndOfBlock) {
                     self.next_token();
-                    match self.parse_statement_block() {
-                        Ok(block) => {
-                            then_statement_block = block;
-                            if self.check_token_and_value(TokenType::Keyword, "else") {
-                                self.next_token();
-                                match self.parse_statement_block() {
-                                    Ok(block) => else_statement_block = Some(block),
-                                    Err(error) => return Err(error),
-                                }
-                            }
-                        },
-                        Err(error) => return Err(error),
-                    }
-                } else {
-                    return Err(self.get_error("Missing closing bracket"));
                 }
+            }
+        }
+        self.next_token();
+        let declaration = StructDeclaration { identifier, fields };
+        // The statement itself is complete, so parsing goes on after it
+        if let Err(error) = self.declare_type(TypeDefinition::Struct(declaration.clone())) {
+            self.diagnostics.push(error);
+        }
+        Ok(StatementKind::Struct(declaration))
+    }
+    fn parse_struct_field(&mut self, identifier: &Identifier, fields: &mut Vec<Identifier>) -> Result<(), Diagnostic> {
+        if !fields.is_empty() {
+            if !self.check_token(TokenType::Separator) {
+                return Err(self.get_error("Missing ',' between fields"));
+            }
+            self.next_token();
+        }
+        if !self.check_token(TokenType::Identifier) {
+            return Err(self.get_error(&format!("Expected a field name, found {}", self.describe_token())));
+        }
+        let field = self.parse_identifier();
+        match fields.iter().find(|previous| previous.name == field.name) {
+            Some(previous) => {
+                let message = format!("Field {} already declared in struct {}", field.name, identifier.name);
+                self.diagnostics.push(Diagnostic::error(ALREADY_DECLARED, &message, field.span.clone())
+                    .with_label(&format!("{} declared again here", field.name))
+                    .with_secondary(previous.span.clone(), &format!("previous declaration of {}", field.name)));
+            },
+            None => fields.push(field),
+        }
+        Ok(())
+    }
+    // enum_declaration ::= enum identifier { variants }
+    // variants ::= variant | variants , variant
+    // variant ::= identifier | identifier (parameters)
+    fn parse_enum_declaration(&mut self) -> Result<StatementKind, Diagnostic> {
+        self.next_token();
+        if !self.check_token(TokenType::Identifier) {
+            return Err(self.get_error("Identifier needed after enum keyword"));
+        }
+        let identifier = self.parse_identifier();
+        if !self.check_token(TokenType::StartOfBlock) {
+            return Err(self.get_error("Missing opening block"));
+        }
+        self.next_token();
+        let mut variants: Vec<VariantDeclaration> = vec![];
+        while !self.check_token(TokenType::EndOfBlock) {
+            if self.current_token.is_none() {
+                return Err(self.get_error("Missing closing block"));
+            }
+            if let Err(error) = self.parse_variant_declaration(&identifier, &mut variants) {
+                if !self.check_token(TokenType::Error) {
+                    self.diagnostics.push(error);
+                }
+                // The enum is declared with the variants before the error
+                while self.current_token.is_some() && !self.check_token(TokenType::EndOfBlock) {
+                    self.next_token();
+                }
+            }
+        }
+        self.next_token();
+        let declaration = EnumDeclaration { identifier, variants };
+        if let Err(error) = self.declare_type(TypeDefinition::Enum(declaration.clone())) {
+            self.diagnostics.push(error);
+        }
+        Ok(StatementKind::Enum(declaration))
+    }
+    fn parse_variant_declaration(&mut self, identifier: &Identifier, variants: &mut Vec<VariantDeclaration>) -> Result<(), Diagnostic> {
+        if !variants.is_empty() {
+            if !self.check_token(TokenType::Separator) {
+                return Err(self.get_error("Missing ',' between variants"));
+            }
+            self.next_token();
+        }
+        if !self.check_token(TokenType::Identifier) {
+            return Err(self.get_error(&format!("Expected a variant name, found {}", self.describe_token())));
+        }
+        let variant = self.parse_identifier();
+        // Field names only document the values, like parameters they must be distinct
+        let fields = if self.check_token_and_value(TokenType::GroupDivider, "(") {
+            self.parse_parameters(&Scope::new(&format!("variant {}", variant.name), None))?
+        } else {
+            vec![]
+        };
+        match variants.iter().find(|previous| previous.identifier.name == variant.name) {
+            Some(previous) => {
+                let message = format!("Variant {} already declared in enum {}", variant.name, identifier.name);
+                self.diagnostics.push(Diagnostic::error(ALREADY_DECLARED, &message, variant.span.clone())
+                    .with_label(&format!("{} declared again here", variant.name))
+                    .with_secondary(previous.identifier.span.clone(), &format!("previous declaration of {}", variant.name)));
+            },
+            None => variants.push(VariantDeclaration {
+                identifier: variant,
+                fields,
+            }),
+        }
+        Ok(())
+    }
+    // Types live in their own namespace, their names being unique in the file
+    fn declare_type(&mut self, definition: TypeDefinition) -> Result<(), Diagnostic> {
+        let identifier = definition.identifier();
+        if let Some(previous) = self.types.get(&identifier.name) {
+            let message = format!("Type {} already declared", identifier.name);
+            return Err(Diagnostic::error(ALREADY_DECLARED, &message, identifier.span.clone())
+                .with_label(&format!("{} declared again here", identifier.name))
+                .with_secondary(previous.identifier().span.clone(), &format!("previous declaration of {}", identifier.name)));
+        }
+        self.types.insert(identifier.name.clone(), definition);
+        Ok(())
+    }
+    // delete_statement ::= delete expression[expression]
+    fn parse_delete_statement(&mut self) -> Result<StatementKind, Diagnostic> {
+        self.next_token();
+        let expression = self.parse_expression()?;
+        let ExpressionKind::Index(target) = expression.kind else {
+            return Err(Diagnostic::error(SYNTAX_ERROR, "Only elements of lists and maps can be deleted", expression.span)
+                .with_label("expected an index like `map[key]`"));
+        };
+        self.parse_end_of_statement()?;
+        Ok(StatementKind::Delete(*target))
+    }
+    // declaration_statement ::= var identifier = expression
+    fn parse_declaration_statement(&mut self) -> Result<StatementKind, Diagnostic> {
+        let declaration = self.parse_declaration()?;
+        self.parse_end_of_statement()?;
+        Ok(StatementKind::Declaration(declaration))
+    }
+    fn parse_declaration(&mut self) -> Result<DeclarationStatement, Diagnostic> {
+        self.next_token();
+        if !self.check_token(TokenType::Identifier) {
+            return Err(self.get_error("Identifier needed after var keyword"));
+        }
+        let identifier = self.parse_identifier();
+        let expression = match self.parse_assigned_expression() {
+            Ok(expression) => expression,
+            Err(error) => {
+                // Declared anyway so that later uses are not reported as undeclared
+                let _ = self.declare(&identifier.name, Symbol::Variable(identifier.span.clone()));
+                return Err(error);
+            }
+        };
+        // Declared after its value so it cannot be used to initialize itself
+        self.declare(&identifier.name, Symbol::Variable(identifier.span.clone()))?;
+        Ok(DeclarationStatement {
+            expression,
+            identifier,
+        })
+    }
+    // assignment_statement ::= identifier = expression
+    fn parse_assignment_statement(&mut self) -> Result<StatementKind, Diagnostic> {
+        let assignment = self.parse_assignment()?;
+        self.parse_end_of_statement()?;
+        Ok(StatementKind::Assignment(assignment))
+    }
+    fn parse_assignment(&mut self) -> Result<AssignmentStatement, Diagnostic> {
+        if !self.check_token(TokenType::Identifier) {
+            return Err(self.get_error("Identifier needed in assignment"));
+        }
+        // Check if identifier is declared in an enclosing scope
+        let identifier = self.parse_identifier();
+        if let Symbol::Function(_, declaration) = self.resolve(&identifier)? {
+            let message = format!("Cannot assign to function {}", identifier.name);
+            return Err(Diagnostic::error(ASSIGNMENT_TO_FUNCTION, &message, identifier.span.clone())
+                .with_label("cannot assign twice to a function")
+                .with_secondary(declaration, "function declared here")
+                .with_help(&format!("declare a variable holding the function with `var {} = ...;`", identifier.name)));
+        }
+        let expression = self.parse_assigned_expression()?;
+        Ok(AssignmentStatement {
+            expression,
+            identifier,
+        })
+    }
+    fn parse_assigned_expression(&mut self) -> Result<Expression, Diagnostic> {
+        if !self.check_token_and_value(TokenType::Operator, "=") {
+            return Err(self.get_error("Assignement without '=' sign"));
+        }
+        self.next_token();
+        self.parse_expression()
+    }
+    fn parse_end_of_statement(&mut self) -> Result<(), Diagnostic> {
+        if !self.check_token(TokenType::EndOfStatement) {
+            // Pointing right after the statement rather than at the next token
+            let end = self.previous_end;
+            let span = Span::new(self.file.clone(), end, (end.0, end.1 + 1));
+            return Err(Diagnostic::error(SYNTAX_ERROR, "Missing end of statement", span).with_label("expected `;`"));
+        }
+        self.next_token();
+        Ok(())
+    }
+    // expression ::= term | expression operator expression | unary_operator expression | (expression)
+    fn parse_expression(&mut self) -> Result<Expression, Diagnostic> {
+        self.parse_binary_expression(0)
+    }
+    // Precedence climbing: only operators binding at least as tight as
+    // `min_precedence` are consumed, the right operand being parsed one level
+    // higher so operators of equal precedence associate to the left.
+    fn parse_binary_expression(&mut self, min_precedence: u8) -> Result<Expression, Diagnostic> {
+        let mut left = self.parse_operand()?;
+        while let Some(operator) = self.current_operator() {
+            let precedence = operator.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            self.next_token();
+            let right = self.parse_binary_expression(precedence + 1)?;
+            left = Expression {
+                span: left.span.to(&right.span),
+                kind: ExpressionKind::Operation(Box::new(Operation {
+                    left,
+                    operator,
+                    right,
+                })),
+            };
+        }
+        Ok(left)
+    }
+    fn parse_operand(&mut self) -> Result<Expression, Diagnostic> {
+        if let Some(operator) = self.current_unary_operator() {
+            // Prefix operators bind tighter than any binary operator
+            let start = self.file_pos;
+            self.next_token();
+            // A `-` before a number is part of the literal, so that the
+            // smallest integer can be written
+            if matches!(operator, UnaryOperator::Negation) && self.check_token(TokenType::Numeric) {
+                let kind = self.parse_number_literal(start, true)?;
+                let span = self.get_span(start);
+                return Ok(Expression {
+                    span: span.clone(),
+                    kind: ExpressionKind::Term(Term { span, kind }),
+                });
+            }
+            let operand = self.parse_operand()?;
+            return Ok(Expression {
+                span: self.get_span(start),
+                kind: ExpressionKind::Unary(Box::new(Unary { operator, operand })),
+            });
+        }
+        let mut expression = self.parse_primary()?;
+        loop {
+            if self.check_token_and_value(TokenType::GroupDivider, "(") {
+                expression = self.parse_call(expression)?;
+            } else if self.check_token_and_value(TokenType::ListDivider, "[") {
+                expression = self.parse_index(expression)?;
+            } else if self.check_token(TokenType::Dot) {
+                expression = self.parse_field(expression)?;
             } else {
-                return Err(self.get_error("Missing opening bracket"));
-            }
-            return Ok(Statement::If(IfStatement {
-                expression,
-                then_statement_block,
-                else_statement_block,
-            }));
-        // declaration_statement ::= var identifier = expression
-        } else if self.check_token_and_value(TokenType::Keyword, "var") {
+                return Ok(expression);
+            }
+        }
+    }
+    fn parse_primary(&mut self) -> Result<Expression, Diagnostic> {
+        let start = self.file_pos;
+        let kind = if self.check_token_and_value(TokenType::GroupDivider, "(") {
+            self.next_token();
+            let expression = self.parse_expression()?;
+            if !self.check_token_and_value(TokenType::GroupDivider, ")") {
+                return Err(self.get_error("Missing closing bracket"));
+            }
+            self.next_token();
+            expression.kind
+        } else if self.check_token_and_value(TokenType::Keyword, "fn") {
+            self.parse_function_literal()?
+        } else if self.check_token(TokenType::TextStart) {
+            self.parse_interpolation()?
+        } else if self.check_token_and_value(TokenType::ListDivider, "[") {
+            self.parse_list()?
+        } else if self.check_token(TokenType::StartOfBlock) {
+            // Blocks only follow statement headers, a brace starting an
+            // expression opens a map
+            self.parse_map()?
+        } else if let Some(declaration) = self.struct_literal_declaration() {
+            self.parse_struct_literal(declaration)?
+        } else if let Some(declaration) = self.variant_literal_declaration() {
+            self.parse_variant_literal(declaration)?
+        } else if self.check_token_and_value(TokenType::Keyword, "match") {
+            self.parse_match()?
+        } else {
+            ExpressionKind::Term(self.parse_term()?)
+        };
+        // Brackets are part of the span of the expression they enclose
+        Ok(Expression {
+            span: self.get_span(start),
+            kind,
+        })
+    }
+    // interpolation ::= "(text | ${expression})*"
+    fn parse_interpolation(&mut self) -> Result<ExpressionKind, Diagnostic> {
+        let mut parts: Vec<InterpolationPart> = vec![];
+        loop {
+            // Text parts surround every embedded expression
+            let is_end = self.check_token(TokenType::TextEnd);
+            let text = self.get_token_value(self.current_token.clone());
+            if !text.is_empty() {
+                parts.push(InterpolationPart::Text(text));
+            }
             self.next_token();
-            if self.check_token(TokenType::Identifier) {
-                // Check if identifier already exist in statement block
-                let identifier_value = self.current_token.clone().unwrap().value;
+            if is_end {
+                return Ok(ExpressionKind::Interpolation(parts));
+            }
+            if self.check_token(TokenType::TextMiddle) || self.check_token(TokenType::TextEnd) {
+                // From the `${` ending the previous text part to the `}`
+                let start = (self.previous_end.0, self.previous_end.1 - 2);
+                let end = self.file_pos;
+                return Err(Diagnostic::error(SYNTAX_ERROR, "Empty `${}` in string", Span::new(self.file.clone(), start, (end.0, end.1 + 1)))
+                    .with_label("no expression to embed")
+                    .with_help("put an expression between the braces, or remove them"));
+            }
+            parts.push(InterpolationPart::Expression(self.parse_expression()?));
+            if !self.check_token(TokenType::TextMiddle) && !self.check_token(TokenType::TextEnd) {
+                return Err(self.get_error(&format!("Expected `}}` after the embedded expression, found {}", self.describe_token())));
+            }
+        }
+    }
+    // list ::= [elements]
+    // elements ::= expression | elements , expression
+    fn parse_list(&mut self) -> Result<ExpressionKind, Diagnostic> {
+        self.next_token();
+        let mut elements: Vec<Expression> = vec![];
+        while !self.check_token_and_value(TokenType::ListDivider, "]") {
+            if !elements.is_empty() {
+                if !self.check_token(TokenType::Separator) {
+                    return Err(self.get_error("Missing ',' between list elements"));
+                }
                 self.next_token();
-                if !block.symbol_table.contains_key(&identifier_value) {
-                    let identifier = Identifier {name: identifier_value.clone(), value: None};
-                    let expression: Expression;
-                    if self.check_token_and_value(TokenType::Operator, "=") {
-                        self.next_token();
-                        match self.parse_expression(block) {
-                            Ok(exp) => expression = exp,
-                            Err(error) => return Err(error),
-                        }
-                        if self.check_token(TokenType::EndOfStatement) {
-                            self.next_token();
-                        } else {
-                            return Err(self.get_error("Missing end of statement"));
-                        }
-                    } else {
-                        return Err(self.get_error("Assignement without '=' sign"));
-                    }
-                    block
-                        .symbol_table
-                        .insert(identifier_value, identifier.clone());
-                    return Ok(Statement::Assignment(AssignmentStatement {
-                        expression,
-                        identifier,
-                    }));
-                } else {
-                    return Err(self.get_error(&format!("Identifier {} already used", identifier_value)));
+            }
+            elements.push(self.parse_expression()?);
+        }
+        self.next_token();
+        Ok(ExpressionKind::List(elements))
+    }
+    // map ::= { entries }
+    // entries ::= expression : expression | entries , expression : expression
+    fn parse_map(&mut self) -> Result<ExpressionKind, Diagnostic> {
+        self.next_token();
+        self.open_literals += 1;
+        let mut entries: Vec<(Expression, Expression)> = vec![];
+        while !self.check_token(TokenType::EndOfBlock) {
+            if !entries.is_empty() {
+                if !self.check_token(TokenType::Separator) {
+                    return Err(self.get_error("Missing ',' between map entries"));
                 }
-            } else {
-                return Err(self.get_error("Identifier needed after var keyword"));
+                self.next_token();
+            }
+            let key = self.parse_expression()?;
+            if !self.check_token(TokenType::Colon) {
+                return Err(self.get_error("Missing ':' after map key"));
             }
-        // assignment_statement ::= identifier = expression
-        } else if self.check_token(TokenType::Identifier) {
-            // Check if identifier already exist in statement block
-            let identifier_value = self.current_token.clone().unwrap().value;
             self.next_token();
-            if block.symbol_table.contains_key(&identifier_value) {
-                let identifier = Identifier {name: identifier_value, value: None};
-                let expression: Expression;
-                if self.check_token_and_value(TokenType::Operator, "=") {
-                    self.next_token();
-                    match self.parse_expression(block) {
-                        Ok(exp) => expression = exp,
-                        Err(error) => return Err(error),
-                    }
-                    if self.check_token(TokenType::EndOfStatement) {
-                        self.next_token();
-                    } else {
-                        return Err(self.get_error("Missing end of statement"));
+            entries.push((key, self.parse_expression()?));
+        }
+        self.next_token();
+        self.open_literals -= 1;
+        Ok(ExpressionKind::Map(entries))
+    }
+    // Declaration of the struct whose literal starts at the current token,
+    // which is the name of a declared struct followed by a brace
+    fn struct_literal_declaration(&mut self) -> Option<StructDeclaration> {
+        if !self.check_token(TokenType::Identifier) || !self.check_peek_and_value(TokenType::StartOfBlock, "{") {
+            return None;
+        }
+        let name = self.get_token_value(self.current_token.clone());
+        match self.types.get(&name) {
+            Some(TypeDefinition::Struct(declaration)) => Some(declaration.clone()),
+            _ => None,
+        }
+    }
+    // struct_literal ::= identifier { field_values }
+    // field_values ::= identifier : expression | field_values , identifier : expression
+    fn parse_struct_literal(&mut self, declaration: StructDeclaration) -> Result<ExpressionKind, Diagnostic> {
+        let start = self.file_pos;
+        let identifier = self.parse_identifier();
+        self.next_token();
+        self.open_literals += 1;
+        let mut fields: Vec<(Identifier, Expression)> = vec![];
+        while !self.check_token(TokenType::EndOfBlock) {
+            if !fields.is_empty() {
+                if !self.check_token(TokenType::Separator) {
+                    return Err(self.get_error("Missing ',' between fields"));
+                }
+                self.next_token();
+            }
+            if !self.check_token(TokenType::Identifier) {
+                return Err(self.get_error(&format!("Expected a field name, found {}", self.describe_token())));
+            }
+            let field = self.parse_identifier();
+            if !declaration.fields.iter().any(|declared| declared.name == field.name) {
+                let names: Vec<&str> = declaration.fields.iter().map(|declared| declared.name.as_str()).collect();
+                return Err(Diagnostic::error(UNKNOWN_FIELD, &format!("Struct {} has no field {}", identifier.name, field.name), field.span.clone())
+                    .with_label("unknown field")
+                    .with_secondary(declaration.identifier.span.clone(), &format!("struct {} declared here", identifier.name))
+                    .with_help(&format!("fields of {} are {}", identifier.name, names.join(", "))));
+            }
+            if let Some((previous, _)) = fields.iter().find(|(previous, _)| previous.name == field.name) {
+                return Err(Diagnostic::error(ALREADY_DECLARED, &format!("Field {} given twice", field.name), field.span.clone())
+                    .with_label(&format!("{} given again here", field.name))
+                    .with_secondary(previous.span.clone(), &format!("first value of {}", field.name)));
+            }
+            if !self.check_token(TokenType::Colon) {
+                return Err(self.get_error("Missing ':' after field name"));
+            }
+            self.next_token();
+            fields.push((field, self.parse_expression()?));
+        }
+        self.next_token();
+        self.open_literals -= 1;
+        let missing: Vec<&str> = declaration
+            .fields
+            .iter()
+            .filter(|declared| !fields.iter().any(|(field, _)| field.name == declared.name))
+            .map(|declared| declared.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            let message = format!("Missing fields {} in {} literal", missing.join(", "), identifier.name);
+            return Err(Diagnostic::error(MISSING_FIELDS, &message, self.get_span(start))
+                .with_label(&format!("missing {}", missing.join(", ")))
+                .with_secondary(declaration.identifier.span.clone(), &format!("struct {} declared here", identifier.name)));
+        }
+        // Values are kept in declaration order, which is the order they are evaluated in
+        fields.sort_by_key(|(field, _)| declaration.fields.iter().position(|declared| declared.name == field.name));
+        Ok(ExpressionKind::Struct(StructLiteral { identifier, fields }))
+    }
+    // Declaration of the enum whose variant starts at the current token,
+    // which is the name of a declared enum followed by a dot
+    fn variant_literal_declaration(&mut self) -> Option<EnumDeclaration> {
+        if !self.check_token(TokenType::Identifier) || !self.check_peek_and_value(TokenType::Dot, ".") {
+            return None;
+        }
+        let name = self.get_token_value(self.current_token.clone());
+        match self.types.get(&name) {
+            Some(TypeDefinition::Enum(declaration)) => Some(declaration.clone()),
+            _ => None,
+        }
+    }
+    // variant_literal ::= identifier.identifier | identifier.identifier (arguments)
+    fn parse_variant_literal(&mut self, declaration: EnumDeclaration) -> Result<ExpressionKind, Diagnostic> {
+        let start = self.file_pos;
+        let enum_name = self.parse_identifier();
+        let (identifier, variant) = self.parse_variant_name(&declaration)?;
+        let mut arguments: Vec<Expression> = vec![];
+        if self.check_token_and_value(TokenType::GroupDivider, "(") {
+            self.next_token();
+            while !self.check_token_and_value(TokenType::GroupDivider, ")") {
+                if !arguments.is_empty() {
+                    if !self.check_token(TokenType::Separator) {
+                        return Err(self.get_error("Missing ',' between arguments"));
                     }
-                } else {
-                    return Err(self.get_error("Assignement without '=' sign"));
+                    self.next_token();
                 }
-                return Ok(Statement::Assignment(AssignmentStatement {
-                    expression,
-                    identifier,
-                }));
-            } else {
-                return Err(self.get_error(&format!("Identifier {} not declared", identifier_value)))
+                arguments.push(self.parse_expression()?);
             }
+            self.next_token();
+        }
+        if arguments.len() != variant.fields.len() {
+            let message = format!(
+                "Variant {}.{} holds {} values but {} were given",
+                enum_name.name,
+                identifier.name,
+                variant.fields.len(),
+                arguments.len()
+            );
+            return Err(Diagnostic::error(ARGUMENT_COUNT, &message, self.get_span(start))
+                .with_label(&format!("{} values given", arguments.len()))
+                .with_secondary(variant.identifier.span.clone(), &format!("variant declared here with {} values", variant.fields.len())));
+        }
+        Ok(ExpressionKind::Variant(VariantLiteral {
+            enum_name,
+            variant: identifier,
+            arguments,
+        }))
+    }
+    // Variant of the enum named after the current `.`, along with its declaration
+    fn parse_variant_name(&mut self, declaration: &EnumDeclaration) -> Result<(Identifier, VariantDeclaration), Diagnostic> {
+        self.next_token();
+        if !self.check_token(TokenType::Identifier) {
+            return Err(self.get_error(&format!("Expected a variant name, found {}", self.describe_token())));
+        }
+        let identifier = self.parse_identifier();
+        match declaration.variants.iter().find(|variant| variant.identifier.name == identifier.name) {
+            Some(variant) => Ok((identifier, variant.clone())),
+            None => {
+                let message = format!("Enum {} has no variant {}", declaration.identifier.name, identifier.name);
+                Err(Diagnostic::error(UNDECLARED_IDENTIFIER, &message, identifier.span.clone())
+                    .with_label("unknown variant")
+                    .with_secondary(declaration.identifier.span.clone(), &format!("enum {} declared here", declaration.identifier.name)))
+            },
         }
-        return Err(self.get_error(&format!(
-            "Statement cannot be matched: {:?}",
-            self.current_token
-        )))
     }
-    fn parse_expression(&mut self, block: &mut StatementBlock) -> Result<Expression, SyntaxError> {
-        if self.check_peek(TokenType::Operator) {
-            let value = self.get_token_value(self.peek_token.clone());
-            let operator: Operator = self.parse_operator(value);
-            let left_expression: Term;
-            match self.parse_term(block) {
-                Ok(term) => left_expression = term,
-                Err(error) => return Err(error),
+    // match ::= match expression { arms }
+    // arms ::= pattern => expression | arms , pattern => expression
+    fn parse_match(&mut self) -> Result<ExpressionKind, Diagnostic> {
+        let start = self.file_pos;
+        self.next_token();
+        let expression = self.parse_expression()?;
+        if !self.check_token(TokenType::StartOfBlock) {
+            return Err(self.get_error("Missing opening block"));
+        }
+        self.next_token();
+        self.open_literals += 1;
+        let mut arms: Vec<MatchArm> = vec![];
+        while !self.check_token(TokenType::EndOfBlock) {
+            if !arms.is_empty() {
+                if !self.check_token(TokenType::Separator) {
+                    return Err(self.get_error("Missing ',' between match arms"));
+                }
+                self.next_token();
+                // The last arm may be followed by a comma
+                if self.check_token(TokenType::EndOfBlock) {
+                    break;
+                }
             }
+            arms.push(self.parse_match_arm()?);
+        }
+        self.next_token();
+        self.open_literals -= 1;
+        // Arms following one matching any value are never tried
+        let catch_all = arms.iter().position(|arm| column_pattern(&arm.pattern).is_none());
+        if let Some(position) = catch_all.filter(|position| position + 1 < arms.len()) {
+            let warning = Diagnostic::warning(UNREACHABLE_CODE, "Unreachable match arm", arms[position + 1].pattern.span.clone())
+                .with_label("unreachable arm")
+                .with_secondary(arms[position].pattern.span.clone(), "this pattern matches any value");
+            self.diagnostics.push(warning);
+        }
+        let rows: Vec<Vec<Option<&Pattern>>> = arms.iter().map(|arm| vec![column_pattern(&arm.pattern)]).collect();
+        if let Some(missing) = missing_values(&rows, 1, &self.types) {
+            let message = format!("Non-exhaustive match, `{}` is not covered", missing[0]);
+            return Err(Diagnostic::error(NON_EXHAUSTIVE_MATCH, &message, self.get_span(start))
+                .with_label(&format!("pattern `{}` not covered", missing[0]))
+                .with_help("add an arm for it, or a `_` arm matching any value"));
+        }
+        Ok(ExpressionKind::Match(Box::new(Match { expression, arms })))
+    }
+    // The bindings of the pattern are only visible in the expression of its arm
+    fn parse_match_arm(&mut self) -> Result<MatchArm, Diagnostic> {
+        let arm_scope = Scope::new("match arm", Some(self.scope.clone()));
+        let pattern = self.parse_pattern(&arm_scope)?;
+        if !self.check_token(TokenType::Arrow) {
+            return Err(self.get_error("Missing '=>' after pattern"));
+        }
+        self.next_token();
+        let enclosing_scope = std::mem::replace(&mut self.scope, arm_scope);
+        let expression = self.parse_expression();
+        self.scope = enclosing_scope;
+        Ok(MatchArm {
+            pattern,
+            expression: expression?,
+        })
+    }
+    // pattern ::= _ | identifier | literal | variant | variant (patterns)
+    // variant ::= identifier | identifier.identifier
+    // patterns ::= pattern | patterns , pattern
+    fn parse_pattern(&mut self, arm_scope: &ScopeRef<Symbol>) -> Result<Pattern, Diagnostic> {
+        let start = self.file_pos;
+        let kind = if self.check_token_and_value(TokenType::Identifier, "_") {
             self.next_token();
-            let right_expresion: Expression;
-            match self.parse_expression(block) {
-                Ok(exp) => right_expresion = exp,
-                Err(error) => return Err(error),
-            }
-            return Ok(Expression::Operation(Box::new(Operation {
-                left: Expression::Term(left_expression),
-                operator,
-                right: right_expresion,
-            })));
-        } else {
-            match self.parse_term(block) {
-                Ok(term) => return Ok(Expression::Term(term)),
-                Err(error) => return Err(error),
+            PatternKind::Wildcard
+        } else if self.check_token(TokenType::Identifier) {
+            match self.pattern_variant()? {
+                Some((enum_name, variant)) => self.parse_variant_pattern(enum_name, variant, arm_scope)?,
+                None => {
+                    let identifier = self.parse_identifier();
+                    let message = format!("Identifier {} already bound in this pattern", identifier.name);
+                    declare_in(arm_scope, &identifier.name, Symbol::Variable(identifier.span.clone()), &message)?;
+                    PatternKind::Binding(identifier)
+                },
             }
-            
+        } else if self.check_token_and_value(TokenType::Operator, "-") && self.check_peek(TokenType::Numeric) {
+            self.next_token();
+            PatternKind::Literal(self.parse_number_literal(start, true)?)
+        } else if self.check_token(TokenType::Numeric)
+            || self.check_token(TokenType::Text)
+            || self.check_token(TokenType::Logical)
+            || self.check_token_and_value(TokenType::Keyword, "nil")
+        {
+            PatternKind::Literal(self.parse_term()?.kind)
+        } else {
+            return Err(self.get_error(&format!("Expected a pattern, found {}", self.describe_token())));
+        };
+        Ok(Pattern {
+            span: self.get_span(start),
+            kind,
+        })
+    }
+    // Enum and variant named by the identifiers starting a pattern, if they
+    // name a variant rather than a binding. A variant may be given without
+    // its enum when no other enum has a variant with the same name.
+    fn pattern_variant(&mut self) -> Result<Option<(String, VariantDeclaration)>, Diagnostic> {
+        if let Some(declaration) = self.variant_literal_declaration() {
+            self.next_token();
+            let (identifier, variant) = self.parse_variant_name(&declaration)?;
+            let fields = variant.fields;
+            return Ok(Some((declaration.identifier.name, VariantDeclaration { identifier, fields })));
         }
+        let name = self.get_token_value(self.current_token.clone());
+        let mut candidates: Vec<(String, Vec<Identifier>)> = self.types.values().filter_map(|definition| match definition {
+            TypeDefinition::Enum(declaration) => declaration
+                .variants
+                .iter()
+                .find(|variant| variant.identifier.name == name)
+                .map(|variant| (declaration.identifier.name.clone(), variant.fields.clone())),
+            TypeDefinition::Struct(_) => None,
+        }).collect();
+        // Sorted so the enums suggested do not depend on the order of the registry
+        candidates.sort_by(|(left, _), (right, _)| left.cmp(right));
+        let mut candidates = candidates.into_iter();
+        let Some((enum_name, fields)) = candidates.next() else {
+            return Ok(None);
+        };
+        if let Some((other, _)) = candidates.next() {
+            let message = format!("Variant {} is declared by several enums", name);
+            return Err(self.get_error(&message)
+                .with_label("ambiguous variant")
+                .with_help(&format!("name its enum, as in `{}.{}` or `{}.{}`", enum_name, name, other, name)));
+        }
+        let identifier = self.parse_identifier();
+        Ok(Some((enum_name, VariantDeclaration { identifier, fields })))
     }
-    fn parse_operator(&mut self, value: String) -> Operator {
-        match value.as_str() {
-            "+" => Operator::Plus,
-            "-" => Operator::Minus,
-            "*" => Operator::Multiplication,
-            "/" => Operator::Division,
-            "%" => Operator::Modulo,
-            "==" => Operator::Equal,
-            "!=" => Operator::NotEqual,
-            "<=" => Operator::InfOrEqual,
-            ">=" => Operator::SupOrEqual,
-            ">" => Operator::Superior,
-            "<" => Operator::Inferior,
-            "&&" => Operator::And,
-            "||" => Operator::Or,
-            _ => panic!("Unknown operator"),
-        }
-    }
-    fn parse_term(&mut self, block: &mut StatementBlock) -> Result<Term, SyntaxError> {
-        if self.check_token(TokenType::Identifier) {
-            let identifier_value = self.current_token.clone().unwrap().value;
+    fn parse_variant_pattern(&mut self, enum_name: String, variant: VariantDeclaration, arm_scope: &ScopeRef<Symbol>) -> Result<PatternKind, Diagnostic> {
+        let start = variant.identifier.span.start;
+        let mut fields: Vec<Pattern> = vec![];
+        if self.check_token_and_value(TokenType::GroupDivider, "(") {
             self.next_token();
-            if block.symbol_table.contains_key(&identifier_value) {
-                let identifier = Identifier {name: identifier_value, value: None};
-                return Ok(Term::Identifier(identifier));
-            } else {
-                return Err(self.get_error(&format!("Identifier {} not declared", identifier_value)));
+            while !self.check_token_and_value(TokenType::GroupDivider, ")") {
+                if !fields.is_empty() {
+                    if !self.check_token(TokenType::Separator) {
+                        return Err(self.get_error("Missing ',' between patterns"));
+                    }
+                    self.next_token();
+                }
+                fields.push(self.parse_pattern(arm_scope)?);
             }
-        } else if self.check_token(TokenType::Numeric) {
-            let token = self.get_token_value(self.current_token.clone());
-            let value = token.parse::<i64>();
             self.next_token();
-            match value {
-                Ok(integer) => return Ok(Term::Integer(integer)),
-                Err(_) => return Err(self.get_error(&format!("Parsing error, {} is not numeric", token))),
+        }
+        if fields.len() != variant.fields.len() {
+            let message = format!(
+                "Variant {}.{} holds {} values but the pattern has {}",
+                enum_name,
+                variant.identifier.name,
+                variant.fields.len(),
+                fields.len()
+            );
+            return Err(Diagnostic::error(ARGUMENT_COUNT, &message, self.get_span(start))
+                .with_label(&format!("expected {} patterns", variant.fields.len())));
+        }
+        Ok(PatternKind::Variant(VariantPattern {
+            enum_name,
+            variant: variant.identifier,
+            fields,
+        }))
+    }
+    // field ::= expression.identifier
+    fn parse_field(&mut self, target: Expression) -> Result<Expression, Diagnostic> {
+        self.next_token();
+        if !self.check_token(TokenType::Identifier) {
+            return Err(self.get_error(&format!("Expected a field name, found {}", self.describe_token())));
+        }
+        let field = self.parse_identifier();
+        // The type of the target is only known when running, but the field
+        // must belong to at least one struct
+        let declared = self.types.values().any(|definition| match definition {
+            TypeDefinition::Struct(declaration) => declaration.fields.iter().any(|declared| declared.name == field.name),
+            TypeDefinition::Enum(_) => false,
+        });
+        if !declared {
+            return Err(Diagnostic::error(UNKNOWN_FIELD, &format!("No struct has a field {}", field.name), field.span.clone())
+                .with_label("unknown field"));
+        }
+        Ok(Expression {
+            span: self.get_span(target.span.start),
+            kind: ExpressionKind::Field(Box::new(Field { target, field })),
+        })
+    }
+    // index ::= expression[expression]
+    fn parse_index(&mut self, collection: Expression) -> Result<Expression, Diagnostic> {
+        self.next_token();
+        let index = self.parse_expression()?;
+        if !self.check_token_and_value(TokenType::ListDivider, "]") {
+            return Err(self.get_error("Missing closing square bracket"));
+        }
+        self.next_token();
+        Ok(Expression {
+            span: self.get_span(collection.span.start),
+            kind: ExpressionKind::Index(Box::new(Index { collection, index })),
+        })
+    }
+    // call ::= expression (arguments)
+    // arguments ::= expression | arguments , expression
+    fn parse_call(&mut self, callee: Expression) -> Result<Expression, Diagnostic> {
+        self.next_token();
+        let mut arguments: Vec<Expression> = vec![];
+        while !self.check_token_and_value(TokenType::GroupDivider, ")") {
+            if !arguments.is_empty() {
+                if !self.check_token(TokenType::Separator) {
+                    return Err(self.get_error("Missing ',' between arguments"));
+                }
+                self.next_token();
+            }
+            arguments.push(self.parse_expression()?);
+        }
+        self.next_token();
+        // Arity is only known when calling a declared function by its name
+        if let ExpressionKind::Term(Term { kind: TermKind::Identifier(identifier), .. }) = &callee.kind {
+            if let Symbol::Function(arity, declaration) = self.resolve(identifier)? {
+                if arguments.len() != arity {
+                    let message = format!(
+                        "Function {} expects {} arguments but {} were given",
+                        identifier.name,
+                        arity,
+                        arguments.len()
+                    );
+                    return Err(Diagnostic::error(ARGUMENT_COUNT, &message, self.get_span(callee.span.start))
+                        .with_label(&format!("{} arguments given", arguments.len()))
+                        .with_secondary(declaration, &format!("function declared here with {} parameters", arity)));
+                }
             }
+        }
+        Ok(Expression {
+            span: self.get_span(callee.span.start),
+            kind: ExpressionKind::Call(Box::new(Call {
+                callee,
+                arguments,
+            })),
+        })
+    }
+    fn current_unary_operator(&mut self) -> Option<UnaryOperator> {
+        if self.check_token_and_value(TokenType::Operator, "!") {
+            Some(UnaryOperator::Not)
+        } else if self.check_token_and_value(TokenType::Operator, "-") {
+            Some(UnaryOperator::Negation)
+        } else {
+            None
+        }
+    }
+    fn current_operator(&mut self) -> Option<Operator> {
+        if self.check_token(TokenType::Operator) {
+            let value = self.get_token_value(self.current_token.clone());
+            self.parse_operator(value)
+        } else {
+            None
+        }
+    }
+    fn parse_operator(&mut self, value: String) -> Option<Operator> {
+        match value.as_str() {
+            "+" => Some(Operator::Plus),
+            "-" => Some(Operator::Minus),
+            "*" => Some(Operator::Multiplication),
+            "/" => Some(Operator::Division),
+            "%" => Some(Operator::Modulo),
+            "==" => Some(Operator::Equal),
+            "!=" => Some(Operator::NotEqual),
+            "<=" => Some(Operator::InfOrEqual),
+            ">=" => Some(Operator::SupOrEqual),
+            ">" => Some(Operator::Superior),
+            "<" => Some(Operator::Inferior),
+            "&&" => Some(Operator::And),
+            "||" => Some(Operator::Or),
+            "??" => Some(Operator::Coalesce),
+            _ => None,
+        }
+    }
+    fn parse_term(&mut self) -> Result<Term, Diagnostic> {
+        let span = self.get_token_span();
+        let kind = self.parse_term_kind()?;
+        Ok(Term { span, kind })
+    }
+    fn parse_term_kind(&mut self) -> Result<TermKind, Diagnostic> {
+        if self.check_token(TokenType::Identifier) {
+            let identifier = self.parse_identifier();
+            self.resolve(&identifier)?;
+            Ok(TermKind::Identifier(identifier))
+        } else if self.check_token(TokenType::Numeric) {
+            self.parse_number_literal(self.file_pos, false)
         } else if self.check_token(TokenType::Logical) {
             let token = self.get_token_value(self.current_token.clone());
             let value = token.parse::<bool>();
             self.next_token();
             match value {
-                Ok(b) => return Ok(Term::Bool(b)),
-                Err(_) => return Err(self.get_error(&format!("Parsing error, {} is not boolean", token))),
+                Ok(b) => Ok(TermKind::Bool(b)),
+                Err(_) => Err(self.get_error(&format!("Parsing error, {} is not boolean", token))),
             }
         } else if self.check_token(TokenType::Text) {
             let text = self.get_token_value(self.current_token.clone());
             self.next_token();
-            return Ok(Term::String(text));
+            Ok(TermKind::String(text))
+        } else if self.check_token_and_value(TokenType::Keyword, "nil") {
+            self.next_token();
+            Ok(TermKind::Nil)
+        } else {
+            Err(self.get_error(&format!("Expected an expression, found {}", self.describe_token())))
+        }
+    }
+    // Number of the current token, which must be numeric, negated when it
+    // follows a `-` starting the literal
+    fn parse_number_literal(&mut self, start: (i32, i32), negative: bool) -> Result<TermKind, Diagnostic> {
+        let token = self.get_token_value(self.current_token.clone());
+        self.next_token();
+        parse_number(&token, negative).map_err(|(message, label)| Diagnostic::error(INVALID_NUMBER, &message, self.get_span(start)).with_label(&label))
+    }
+    // Identifier made of the current token, which must be an identifier
+    fn parse_identifier(&mut self) -> Identifier {
+        let identifier = Identifier {
+            span: self.get_token_span(),
+            name: self.get_token_value(self.current_token.clone()),
+        };
+        self.next_token();
+        identifier
+    }
+    // Span from the start position to the end of the last consumed token
+    fn get_span(&self, start: (i32, i32)) -> Span {
+        Span::new(self.file.clone(), start, self.previous_end)
+    }
+    fn get_token_span(&self) -> Span {
+        match &self.current_token {
+            Some(token) => Span::new(self.file.clone(), token.pos, token.end),
+            // Past the last token, pointing right after it
+            None => Span::new(self.file.clone(), self.previous_end, (self.previous_end.0, self.previous_end.1 + 1)),
         }
-        return Err(self.get_error(&format!(
-            "Term cannot be matched: {:?}",
-            self.current_token
-        )))
     }
-    fn get_error(&mut self, message: &str) -> SyntaxError {
-        SyntaxError { line: self.file_pos.0, col: self.file_pos.1, message: message.to_owned()}
+    fn describe_token(&self) -> String {
+        match &self.current_token {
+            // Text following an embedded expression starts with its closing brace
+            Some(token) if matches!(token.token_type, TokenType::TextMiddle | TokenType::TextEnd) => "`}`".to_owned(),
+            Some(token) => format!("`{}`", token.value),
+            None => "end of file".to_owned(),
+        }
+    }
+    fn get_error(&mut self, message: &str) -> Diagnostic {
+        Diagnostic::error(SYNTAX_ERROR, message, self.get_token_span())
+    }
+}
+
+// Value of a numeric literal, negative when it follows a `-`, or the error
+// message and label explaining why it is invalid
+fn parse_number(literal: &str, negative: bool) -> Result<TermKind, (String, String)> {
+    let number = literal.replace('_', "");
+    let sign = if negative { "-" } else { "" };
+    let literal = format!("{}{}", sign, literal);
+    let (radix, digits) = match number.get(..2) {
+        Some("0x") => (16, &number[2..]),
+        Some("0b") => (2, &number[2..]),
+        Some("0o") => (8, &number[2..]),
+        // Decimal numbers with a fractional part or an exponent are floats
+        _ if number.contains(['.', 'e', 'E']) => {
+            let exponent = number.find(['e', 'E']).map(|position| number[position + 1..].trim_start_matches(['+', '-']));
+            if exponent.is_some_and(str::is_empty) {
+                return Err((format!("Invalid float literal `{}`", literal), "exponent has no digits".to_owned()));
+            }
+            return number
+                .parse::<f64>()
+                .map(|float| TermKind::Float(if negative { -float } else { float }))
+                .map_err(|_| (format!("Invalid float literal `{}`", literal), "not a valid float".to_owned()));
+        },
+        _ => (10, &number[..]),
+    };
+    if digits.is_empty() {
+        return Err((format!("Integer literal `{}` has no digits", literal), "expected digits after the prefix".to_owned()));
+    }
+    if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+        let message = format!("Invalid digit `{}` in base {} literal `{}`", digit, radix, literal);
+        return Err((message, format!("digits of base {} integers are 0 to {}", radix, std::char::from_digit(radix - 1, radix).unwrap_or('9'))));
+    }
+    // Parsed with its sign, the smallest integer having no positive counterpart
+    i64::from_str_radix(&format!("{}{}", sign, digits), radix).map(TermKind::Integer).map_err(|_| {
+        if negative {
+            (format!("Integer literal `{}` is too small", literal), format!("integers are at least {}", i64::MIN))
+        } else {
+            (format!("Integer literal `{}` is too large", literal), format!("integers are at most {}", i64::MAX))
+        }
+    })
+}
+
+// Pattern of a cell of a pattern matrix, `None` standing for patterns
+// matching any value
+fn column_pattern(pattern: &Pattern) -> Option<&Pattern> {
+    match pattern.kind {
+        PatternKind::Wildcard | PatternKind::Binding(_) => None,
+        _ => Some(pattern),
+    }
+}
+
+// Values not matched by any row of the pattern matrix, one pattern for each of
+// its columns, or `None` when the rows match every value. Enums and booleans
+// are the only types whose values can all be listed, any other column needing
+// a pattern matching any value.
+fn missing_values(rows: &[Vec<Option<&Pattern>>], width: usize, types: &HashMap<String, TypeDefinition>) -> Option<Vec<String>> {
+    if width == 0 {
+        return if rows.is_empty() { Some(vec![]) } else { None };
+    }
+    // Name, printed form and number of values of every constructor of the
+    // type of the first column
+    let mut constructors: Vec<(String, String, usize)> = vec![];
+    for row in rows {
+        match row[0].map(|pattern| &pattern.kind) {
+            Some(PatternKind::Variant(variant)) => {
+                if let Some(TypeDefinition::Enum(declaration)) = types.get(&variant.enum_name) {
+                    constructors = declaration
+                        .variants
+                        .iter()
+                        .map(|variant| {
+                            let name = variant.identifier.name.clone();
+                            (name.clone(), format!("{}.{}", declaration.identifier.name, name), variant.fields.len())
+                        })
+                        .collect();
+                }
+                break;
+            },
+            Some(PatternKind::Literal(TermKind::Bool(_))) => {
+                constructors = vec![("true".to_owned(), "true".to_owned(), 0), ("false".to_owned(), "false".to_owned(), 0)];
+                break;
+            },
+            _ => {},
+        }
+    }
+    if constructors.is_empty() {
+        // Only the rows matching any value cover the values no pattern lists
+        let rest: Vec<Vec<Option<&Pattern>>> = rows.iter().filter(|row| row[0].is_none()).map(|row| row[1..].to_vec()).collect();
+        let mut missing = missing_values(&rest, width - 1, types)?;
+        missing.insert(0, "_".to_owned());
+        return Some(missing);
+    }
+    for (name, printed, arity) in constructors {
+        // Rows matching the constructor, with the patterns of its values first
+        let specialized: Vec<Vec<Option<&Pattern>>> = rows
+            .iter()
+            .filter_map(|row| {
+                let mut fields: Vec<Option<&Pattern>> = match row[0].map(|pattern| &pattern.kind) {
+                    None => vec![None; arity],
+                    Some(PatternKind::Variant(variant)) if variant.variant.name == name => {
+                        variant.fields.iter().map(column_pattern).collect()
+                    },
+                    Some(PatternKind::Literal(TermKind::Bool(b))) if b.to_string() == name => vec![],
+                    _ => return None,
+                };
+                fields.extend_from_slice(&row[1..]);
+                Some(fields)
+            })
+            .collect();
+        if let Some(missing) = missing_values(&specialized, arity + width - 1, types) {
+            let (values, rest) = missing.split_at(arity);
+            let head = if arity == 0 { printed } else { format!("{}({})", printed, values.join(", ")) };
+            return Some(std::iter::once(head).chain(rest.iter().cloned()).collect());
+        }
+    }
+    None
+}
+
+// Declare a symbol in a scope, reporting the previous declaration if any
+fn declare_in(scope: &ScopeRef<Symbol>, name: &str, symbol: Symbol, message: &str) -> Result<(), Diagnostic> {
+    if scope.borrow().is_declared_locally(name) {
+        let previous = scope.borrow().get(name);
+        let mut error = Diagnostic::error(ALREADY_DECLARED, message, symbol.span().clone())
+            .with_label(&format!("{} declared again here", name));
+        if let Some(previous) = previous {
+            error = error.with_secondary(previous.span().clone(), &format!("previous declaration of {}", name));
+        }
+        return Err(error);
+    }
+    scope.borrow_mut().declare(name, symbol);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Severity;
+    use crate::lexer::LexicalParser;
+
+    // AST and diagnostics of the source, which must lex without errors
+    fn parse(source: &str) -> (StatementBlock, Vec<Diagnostic>) {
+        let (tokens, errors) = LexicalParser::new(source.to_owned(), "test.toy").parse();
+        assert!(errors.is_empty(), "errors lexing `{}`", source);
+        SyntaxAnalizer::new(tokens, "test.toy").parse()
+    }
+
+    // Code and message of every diagnostic of the source
+    fn diagnostics(source: &str) -> Vec<(&'static str, String)> {
+        parse(source).1.into_iter().map(|diagnostic| (diagnostic.code, diagnostic.message)).collect()
+    }
+
+    // Error of a match missing the pattern
+    fn not_covered(pattern: &str) -> Vec<(&'static str, String)> {
+        vec![(NON_EXHAUSTIVE_MATCH, format!("Non-exhaustive match, `{}` is not covered", pattern))]
+    }
+
+    #[test]
+    fn nested_variants_must_all_be_matched() {
+        let missing = "{
+            enum O { Some(value), None }
+            var o = O.Some(O.None);
+            var x = match o { Some(Some(v)) => v, None => 0 };
+        }";
+        assert_eq!(diagnostics(missing), not_covered("O.Some(O.None)"));
+        let complete = "{
+            enum O { Some(value), None }
+            var o = O.Some(O.None);
+            var x = match o { Some(Some(v)) => v, Some(None) => 1, None => 0 };
+        }";
+        assert_eq!(diagnostics(complete), vec![]);
+    }
+
+    #[test]
+    fn booleans_must_both_be_matched() {
+        assert_eq!(diagnostics("{ var b = true; var x = match b { true => 1 }; }"), not_covered("false"));
+        assert_eq!(diagnostics("{ var b = true; var x = match b { false => 0, true => 1 }; }"), vec![]);
+    }
+
+    #[test]
+    fn literal_arms_need_an_arm_matching_any_value() {
+        assert_eq!(diagnostics("{ var n = 1; var x = match n { 1 => 'one', 2 => 'two' }; }"), not_covered("_"));
+        assert_eq!(diagnostics("{ var n = 1; var x = match n { 1 => 'one', other => 'many' }; }"), vec![]);
+        assert_eq!(diagnostics("{ var n = 1; var x = match n { 1 => 'one', _ => 'many' }; }"), vec![]);
+    }
+
+    #[test]
+    fn arms_after_a_wildcard_are_unreachable() {
+        let (_, found) = parse("{ var n = 1; var x = match n { _ => 0, 1 => 1 }; }");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].severity, Severity::Warning);
+        assert_eq!(found[0].code, UNREACHABLE_CODE);
+        assert_eq!(found[0].message, "Unreachable match arm");
+    }
+
+    #[test]
+    fn bare_variants_of_several_enums_are_ambiguous() {
+        let enums = "enum A { Empty } enum B { Empty } var a = A.Empty;";
+        let (_, found) = parse(&format!("{{ {} var x = match a {{ Empty => 0 }}; }}", enums));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].code, SYNTAX_ERROR);
+        assert_eq!(found[0].message, "Variant Empty is declared by several enums");
+        assert_eq!(found[0].help.as_deref(), Some("name its enum, as in `A.Empty` or `B.Empty`"));
+        assert_eq!(diagnostics(&format!("{{ {} var x = match a {{ A.Empty => 0 }}; }}", enums)), vec![]);
+    }
+
+    // Position of the primary label of the diagnostic, lines and columns
+    // starting at 0
+    fn position(diagnostic: &Diagnostic) -> (i32, i32) {
+        diagnostic.labels[0].span.start
+    }
+
+    // Integer printed by the statement, if it prints one
+    fn printed(statement: &Statement) -> Option<i64> {
+        match &statement.kind {
+            StatementKind::Print(PrintStatement::Expression(Expression { kind: ExpressionKind::Term(term), .. })) => match term.kind {
+                TermKind::Integer(value) => Some(value),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn errors_in_nested_literals_skip_their_statement_only() {
+        // Source, error, its position and statements left in the AST
+        let sources = [
+            ("{ var m = { 'a': { 'b': 1 2 } }; print(1); }", "Missing ',' between map entries", (0, 26), 1),
+            ("{ struct P { x } var p = P { x: 1 2 }; print(1); }", "Missing ',' between fields", (0, 34), 2),
+            ("{ var x = match 1 { 1 => 2 3 }; print(1); }", "Missing ',' between match arms", (0, 27), 1),
+            ("{ var m = { 'a': match 1 { _ => { 'b': 1 2 } } }; print(1); }", "Missing ',' between map entries", (0, 41), 1),
+        ];
+        for (source, message, start, statements) in sources {
+            let (ast, found) = parse(source);
+            assert_eq!(found.len(), 1, "diagnostics of `{}`: {:?}", source, found);
+            assert_eq!((found[0].code, found[0].message.as_str(), position(&found[0])), (SYNTAX_ERROR, message, start));
+            assert_eq!(ast.statements.len(), statements, "statements of `{}`", source);
+            assert_eq!(printed(ast.statements.last().unwrap()), Some(1), "last statement of `{}`", source);
+        }
+    }
+
+    #[test]
+    fn errors_in_a_function_literal_skip_the_statement_of_its_body() {
+        let (ast, found) = parse("{ var f = fn (x) { var = 1; return x; }; print(1); }");
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert_eq!(found[0].message, "Identifier needed after var keyword");
+        assert_eq!(position(&found[0]), (0, 23));
+        assert_eq!(ast.statements.len(), 2);
+        let StatementKind::Declaration(declaration) = &ast.statements[0].kind else {
+            panic!("expected the declaration of f, found {:?}", ast.statements[0]);
+        };
+        let ExpressionKind::Function(function) = &declaration.expression.kind else {
+            panic!("expected a function literal, found {:?}", declaration.expression);
+        };
+        let body = &function.statement_block.statements;
+        assert_eq!(body.len(), 1);
+        assert!(matches!(body[0].kind, StatementKind::Return(Some(_))));
+        assert_eq!(printed(&ast.statements[1]), Some(1));
+    }
+
+    #[test]
+    fn missing_semicolons_skip_to_the_end_of_the_next_statement() {
+        let (ast, found) = parse("{ var a = 1 print(a); var b = 2; print(3); }");
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert_eq!(found[0].message, "Missing end of statement");
+        assert_eq!(found[0].labels[0].message, "expected `;`");
+        assert_eq!(position(&found[0]), (0, 11));
+        assert_eq!(ast.statements.len(), 2);
+        assert!(matches!(&ast.statements[0].kind, StatementKind::Declaration(declaration) if declaration.identifier.name == "b"));
+        assert_eq!(printed(&ast.statements[1]), Some(3));
+    }
+
+    #[test]
+    fn empty_interpolations_are_reported() {
+        let (_, found) = parse("{ print(\"a${}b\"); }");
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert_eq!(found[0].message, "Empty `${}` in string");
+        assert_eq!((found[0].labels[0].span.start, found[0].labels[0].span.end), ((0, 10), (0, 13)));
+        assert_eq!(diagnostics("{ print(\"a${1 +}b\"); }"), vec![(SYNTAX_ERROR, "Expected an expression, found `}`".to_owned())]);
+    }
+
+    #[test]
+    fn exponents_without_digits_are_invalid_numbers() {
+        for number in ["1.5e", "2e+", "-3E-"] {
+            let (_, found) = parse(&format!("{{ print({}); }}", number));
+            assert_eq!(found.len(), 1, "{:?}", found);
+            assert_eq!((found[0].code, found[0].message.clone()), (INVALID_NUMBER, format!("Invalid float literal `{}`", number)));
+            assert_eq!(found[0].labels[0].message, "exponent has no digits");
+        }
     }
 }
@@ -0,0 +1,64 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Shared handle on a scope, closures keeping their defining scope alive
+/// after the block that created it has returned.
+pub type ScopeRef<T> = Rc<RefCell<Scope<T>>>;
+
+/// Symbols declared by a block, linked to the scope of the enclosing block.
+/// The parser stores what kind of symbol each identifier is, the interpreter
+/// stores its value.
+#[derive(Debug)]
+pub struct Scope<T> {
+    name: String,
+    symbols: HashMap<String, T>,
+    parent: Option<ScopeRef<T>>,
+}
+
+impl<T: Clone> Scope<T> {
+    pub fn new(name: &str, parent: Option<ScopeRef<T>>) -> ScopeRef<T> {
+        Rc::new(RefCell::new(Scope {
+            name: name.to_owned(),
+            symbols: HashMap::new(),
+            parent,
+        }))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Create a symbol in this scope, shadowing any outer one.
+    pub fn declare(&mut self, name: &str, symbol: T) {
+        self.symbols.insert(name.to_owned(), symbol);
+    }
+
+    pub fn is_declared_locally(&self, name: &str) -> bool {
+        self.symbols.contains_key(name)
+    }
+
+    /// Update the closest symbol with this name, returns false when no scope
+    /// of the chain declares it.
+    pub fn assign(&mut self, name: &str, symbol: T) -> bool {
+        if let Some(current) = self.symbols.get_mut(name) {
+            *current = symbol;
+            return true;
+        }
+        match &self.parent {
+            Some(parent) => parent.borrow_mut().assign(name, symbol),
+            None => false,
+        }
+    }
+
+    /// Resolve a name from this scope up to the outermost one.
+    pub fn get(&self, name: &str) -> Option<T> {
+        match self.symbols.get(name) {
+            Some(symbol) => Some(symbol.clone()),
+            None => match &self.parent {
+                Some(parent) => parent.borrow().get(name),
+                None => None,
+            },
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// Region of a source file, from the start position included to the end
+/// position excluded, lines starting at 0 like token positions.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Span {
+    // Shared by every node of the file
+    pub file: Arc<str>,
+    pub start: (i32, i32),
+    pub end: (i32, i32),
+}
+impl Span {
+    pub fn new(file: Arc<str>, start: (i32, i32), end: (i32, i32)) -> Self {
+        Span { file, start, end }
+    }
+    /// Span going from the start of this span to the end of the other one.
+    pub fn to(&self, other: &Span) -> Span {
+        Span::new(self.file.clone(), self.start, other.end)
+    }
+}
+// Kept on one line as it is part of every node of a printed AST
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}.{}-{}.{}",
+            self.file,
+            self.start.0 + 1,
+            self.start.1,
+            self.end.0 + 1,
+            self.end.1
+        )
+    }
+}
+// Location of the start of the span, as understood by editors
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.start.0 + 1, self.start.1 + 1)
+    }
+}
+
+/// Start offsets of the lines of a source buffer, turning byte offsets into
+/// positions.
+#[derive(Debug)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(offset, _)| offset + 1));
+        LineIndex { line_starts }
+    }
+    /// Line and column of a byte offset, both starting at 0.
+    pub fn position(&self, offset: usize) -> (i32, i32) {
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        (line as i32, (offset - self.line_starts[line]) as i32)
+    }
+}
@@ -0,0 +1,38 @@
+use compiler::errors::CALL_DEPTH;
+use compiler::grammar::StatementBlock;
+use compiler::interpreter;
+use compiler::lexer::LexicalParser;
+use compiler::parser::SyntaxAnalizer;
+
+// AST of a program free of errors
+fn parse(program: &str) -> StatementBlock {
+    let (tokens, errors) = LexicalParser::new(program.to_owned(), "program.toy").parse();
+    assert!(errors.is_empty());
+    let (ast, errors) = SyntaxAnalizer::new(tokens, "program.toy").parse();
+    assert!(errors.is_empty());
+    ast
+}
+
+#[test]
+fn deep_recursion_stops_at_the_call_depth_limit_on_a_default_stack() {
+    // Recursing deeper than any call depth limit
+    let ast = parse("{
+        fn recurse(n) {
+            if (n == 0) { return 0; }
+            return 1 + recurse(n - 1);
+        }
+        print(recurse(100000));
+    }");
+    let error = interpreter::interpret(ast, false).unwrap_err();
+    assert_eq!(error.code, CALL_DEPTH);
+}
+
+#[test]
+fn functions_can_call_functions_declared_after_them() {
+    let ast = parse("{
+        fn is_even(n) { if (n == 0) { return true; } return is_odd(n - 1); }
+        fn is_odd(n) { if (n == 0) { return false; } return is_even(n - 1); }
+        if (!is_even(10)) { print(1 / 0); }
+    }");
+    assert!(interpreter::interpret(ast, false).is_ok());
+}
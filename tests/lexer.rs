@@ -0,0 +1,26 @@
+mod regex_lexer;
+
+use compiler::lexer::LexicalParser;
+
+// Program using every token the regex lexer knew
+const PROGRAM: &str = "{
+    fn fibonacci(n) {
+        if (n <= 1 || n == 0 && !false) { return n; } else { print('recursing'); }
+        return fibonacci(n - 1) + fibonacci(n - 2);
+    }
+    var total = 0;
+    for (var i = 0; i < 10; i = i + 1) {
+        if (i % 2 != 0) { continue; }
+        total = total * 3 / 2 - fibonacci(i);
+    }
+    for k in 0..total { print(k >= 5); }
+    while (total > 0) { total = total - 1; break; }
+    print(true);
+}";
+
+#[test]
+fn scanner_gives_the_tokens_of_the_regex_lexer() {
+    let (tokens, errors) = LexicalParser::new(PROGRAM.to_owned(), "program.toy").parse();
+    assert!(errors.is_empty());
+    assert_eq!(regex_lexer::describe(&tokens), regex_lexer::describe(&regex_lexer::lex(PROGRAM)));
+}
@@ -0,0 +1,66 @@
+//! Regex lexer the hand-written scanner replaced, kept to check that both
+//! give the same tokens and to measure the speedup. It only knows the tokens
+//! the language had then, without comments, escapes or interpolation.
+use compiler::lexer::{Token, TokenType};
+use regex::Regex;
+
+// Patterns tried in order at each position. Keywords and booleans only match
+// whole words, as the scanner does, the original patterns splitting `format`
+// in `for` and `mat`.
+fn patterns() -> Vec<(TokenType, &'static str)> {
+    vec![
+        (TokenType::Whitespace, "[\\s\\t\\n\\r]"),
+        (TokenType::Keyword, "(var|if|else|print|while|for|break|continue|fn|return)\\b"),
+        (TokenType::GroupDivider, "(\\(|\\))"),
+        (TokenType::StartOfBlock, "(\\{)"),
+        (TokenType::EndOfBlock, "(\\})"),
+        (TokenType::EndOfStatement, "(;)"),
+        (TokenType::Separator, "(,)"),
+        (TokenType::Range, "(\\.\\.)"),
+        (TokenType::Numeric, "[0-9]+"),
+        (TokenType::Text, "'([^']*)'"),
+        (TokenType::Logical, "(true|false)\\b"),
+        (TokenType::Identifier, "[a-zA-Z_]+[a-zA-Z0-9_]*"),
+        (TokenType::Operator, "(\\+|\\-|&&|\\|\\||>=|<=|==|!=|>|<|=|\\!|\\/|\\*|\\%)"),
+    ]
+}
+
+/// Tokens of a valid program, lexed line by line and compiling the pattern
+/// of every token type tried like the original lexer did.
+pub fn lex(source: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    for (line, text) in source.lines().enumerate() {
+        let mut col = 0;
+        while col < text.len() {
+            let rest = &text[col..];
+            let (token_type, captures) = patterns()
+                .into_iter()
+                .find_map(|(token_type, pattern)| {
+                    let captures = Regex::new(&format!("^{}", pattern)).unwrap().captures(rest)?;
+                    Some((token_type, captures))
+                })
+                .unwrap_or_else(|| panic!("No token matches `{}`", rest));
+            let length = captures[0].len();
+            if token_type != TokenType::Whitespace {
+                // The first group holds the content of strings, without quotes
+                let value = captures.get(1).unwrap_or_else(|| captures.get(0).unwrap());
+                tokens.push(Token {
+                    pos: (line as i32, col as i32),
+                    end: (line as i32, (col + length) as i32),
+                    token_type,
+                    value: value.as_str().to_owned(),
+                });
+            }
+            col += length;
+        }
+    }
+    tokens
+}
+
+/// Type, value and position of every token, for comparing token streams.
+pub fn describe(tokens: &[Token]) -> Vec<String> {
+    tokens
+        .iter()
+        .map(|token| format!("{:?} {:?} {:?}-{:?}", token.token_type, token.value, token.pos, token.end))
+        .collect()
+}